@@ -0,0 +1,61 @@
+// Steps a batch of headless environments with the built-in bot as the policy
+// and reports how fast they run.
+//
+//     cargo run --release --example env_benchmark [ENVS] [THREADS] [STEPS]
+
+use std::time::Instant;
+
+use asteroids::controls::Devices;
+use asteroids::env::{EnvConfig, VecEnv};
+use asteroids::pilot::{HeuristicBot, ShipController};
+
+fn main() {
+    let mut args = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse::<usize>().expect("arguments must be numbers"));
+    let count = args.next().unwrap_or(16);
+    let threads = args.next().unwrap_or(4);
+    let steps = args.next().unwrap_or(2000);
+
+    let mut envs = VecEnv::new(count, EnvConfig::default(), threads);
+    let mut observations = envs.reset(&(0..count as u64).collect::<Vec<_>>());
+    let mut next_seed = count as u64;
+
+    let mut episode_rewards = vec![0.0; count];
+    let mut finished = Vec::new();
+
+    let start = Instant::now();
+    for _ in 0..steps {
+        let actions: Vec<_> = observations
+            .iter()
+            .map(|observation| HeuristicBot.act(observation, &Devices::default()))
+            .collect();
+
+        for (index, step) in envs.step(&actions).into_iter().enumerate() {
+            episode_rewards[index] += step.reward;
+            observations[index] = step.observation;
+
+            if step.done {
+                finished.push(episode_rewards[index]);
+                episode_rewards[index] = 0.0;
+                observations[index] = envs.reset_one(index, next_seed);
+                next_seed += 1;
+            }
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} environments on {} threads: {:.0} steps per second",
+        count,
+        threads,
+        (count * steps) as f64 / elapsed
+    );
+    if !finished.is_empty() {
+        println!(
+            "{} episodes finished, mean reward {:.1}",
+            finished.len(),
+            finished.iter().sum::<f32>() / finished.len() as f32
+        );
+    }
+}
@@ -0,0 +1,80 @@
+// A remote bot that flies over the JSON protocol in `remote.rs`, using the
+// built-in autopilot to pick its actions. Anything that can open a socket and
+// read JSON could stand in for it.
+//
+// To fly player 1, start a game with
+//
+//     asteroids --remote-bot 1 --lockstep
+//
+// and run `cargo run --example remote_bot`, adding the game's address if it
+// isn't on the default port, e.g. `cargo run --example remote_bot -- 127.0.0.1:7878`.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use asteroids::controls::Devices;
+use asteroids::pilot::{HeuristicBot, ShipController};
+use asteroids::remote::{self, BotMessage, GameMessage};
+
+fn main() {
+    let address = std::env::args().nth(1).map_or_else(
+        || SocketAddr::from(([127, 0, 0, 1], remote::DEFAULT_PORT)),
+        |address| {
+            address
+                .parse::<SocketAddr>()
+                .expect("expected an address like 127.0.0.1:7878")
+        },
+    );
+
+    play(address);
+}
+
+// Plays until the game hangs up
+fn play(address: SocketAddr) {
+    // The game may still be starting up
+    let stream = (0..50)
+        .find_map(|_| {
+            TcpStream::connect(address)
+                .map_err(|_| thread::sleep(Duration::from_millis(100)))
+                .ok()
+        })
+        .expect("failed to connect to the game");
+    let mut writer = stream.try_clone().expect("failed to clone stream");
+    let reader = BufReader::new(stream);
+
+    let mut bot = HeuristicBot;
+    let mut frames = 0;
+    let mut score = 0;
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        match serde_json::from_str(&line).expect("bad message from the game") {
+            GameMessage::Welcome { player, lockstep } => {
+                println!("flying player {} (lock-step: {})", player + 1, lockstep);
+            }
+            GameMessage::Observation { observation, .. } => {
+                let action = bot.act(&observation, &Devices::default());
+                let mut message = serde_json::to_vec(&BotMessage::Action(action)).unwrap();
+                message.push(b'\n');
+                if writer.write_all(&message).is_err() {
+                    break;
+                }
+
+                frames += 1;
+                score = observation.score;
+            }
+            GameMessage::GameOver { .. } => {
+                println!("game over");
+                break;
+            }
+        }
+    }
+
+    println!("saw {} frames, scored {}", frames, score);
+}
@@ -0,0 +1,11 @@
+"""Headless Asteroids for reinforcement learning.
+
+``Env`` plays one single-player game and ``VecEnv`` steps many at once on
+native threads. Both run the game's own simulation, so agents learn the same
+game people play. ``AsteroidsEnv`` in ``asteroids_env.gym`` wraps ``Env`` for
+Gymnasium and needs the ``gym`` extra.
+"""
+
+from ._native import ACTION_SIZE, ASTEROID_FEATURES, SHIP_FEATURES, Env, VecEnv
+
+__all__ = ["ACTION_SIZE", "ASTEROID_FEATURES", "SHIP_FEATURES", "Env", "VecEnv"]
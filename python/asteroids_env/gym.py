@@ -0,0 +1,33 @@
+"""A Gymnasium wrapper around ``Env``.
+
+Actions are ``MultiBinary(6)``: thrust, reverse, left, right, fire,
+hyperspace. Episodes terminate when the ship is out of lives and are truncated
+once ``max_steps`` steps have been taken, if set.
+"""
+
+import gymnasium as gym
+import numpy as np
+
+from ._native import ACTION_SIZE, Env
+
+
+class AsteroidsEnv(gym.Env):
+    metadata = {"render_modes": []}
+
+    def __init__(self, **config):
+        self._env = Env(**config)
+        self.observation_space = gym.spaces.Box(
+            -np.inf, np.inf, shape=(self._env.observation_size,), dtype=np.float32
+        )
+        self.action_space = gym.spaces.MultiBinary(ACTION_SIZE)
+
+    def reset(self, *, seed=None, options=None):
+        super().reset(seed=seed)
+        if seed is None:
+            seed = int(self.np_random.integers(2**63))
+        return self._env.reset(seed), {}
+
+    def step(self, action):
+        observation, reward, done = self._env.step(np.asarray(action, dtype=np.float32).tolist())
+        terminated = self._env.is_over()
+        return observation, reward, terminated, done and not terminated, {}
@@ -0,0 +1,353 @@
+// Python bindings for the reinforcement-learning environment in
+// `asteroids::env`, so agents are trained on the very game people play.
+// Observations come out as flat float32 NumPy arrays and actions go in as six
+// numbers, each of which presses its control when above 0.5:
+//
+//     thrust, reverse, left, right, fire, hyperspace
+//
+// An observation is the ship's own state followed by the nearest asteroids:
+//
+//     x, y, velocity x, velocity y, cos heading, sin heading, hyperspace ready, lives
+//     then per asteroid: present, offset x, offset y, velocity x, velocity y, size
+//
+// Positions and speeds are scaled to roughly -1..1. Missing asteroids are all
+// zeros, including the `present` flag.
+
+use asteroids::controls::ShipInput;
+use asteroids::env::{Env, EnvConfig, RewardConfig, Step, VecEnv};
+use asteroids::pilot::{Observation, OBSERVATION_RANGE};
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+const SHIP_FEATURES: usize = 8;
+const ASTEROID_FEATURES: usize = 6;
+const ACTION_SIZE: usize = 6;
+
+// Scales for speeds, a little over the fastest ship and asteroid
+const SHIP_SPEED_SCALE: f32 = 500.0;
+const ASTEROID_SPEED_SCALE: f32 = 400.0;
+
+fn observation_size(max_asteroids: usize) -> usize {
+    SHIP_FEATURES + ASTEROID_FEATURES * max_asteroids
+}
+
+fn features(observation: &Observation, max_asteroids: usize) -> Vec<f32> {
+    let mut features = Vec::with_capacity(observation_size(max_asteroids));
+
+    features.extend([
+        observation.position.x / 640.0,
+        observation.position.y / 360.0,
+        observation.velocity.x / SHIP_SPEED_SCALE,
+        observation.velocity.y / SHIP_SPEED_SCALE,
+        observation.heading.cos(),
+        observation.heading.sin(),
+        observation.hyperspace_ready as i32 as f32,
+        observation.lives as f32,
+    ]);
+
+    for asteroid in observation.asteroids.iter().take(max_asteroids) {
+        features.extend([
+            1.0,
+            asteroid.offset.x / OBSERVATION_RANGE,
+            asteroid.offset.y / OBSERVATION_RANGE,
+            asteroid.velocity.x / ASTEROID_SPEED_SCALE,
+            asteroid.velocity.y / ASTEROID_SPEED_SCALE,
+            asteroid.size as f32 / 3.0,
+        ]);
+    }
+    features.resize(observation_size(max_asteroids), 0.0);
+
+    features
+}
+
+fn action(values: &[f32]) -> PyResult<ShipInput> {
+    if values.len() != ACTION_SIZE {
+        return Err(PyValueError::new_err(format!(
+            "expected {} action values, got {}",
+            ACTION_SIZE,
+            values.len()
+        )));
+    }
+
+    let pressed = |index: usize| values[index] > 0.5;
+    Ok(ShipInput {
+        thrust: pressed(0),
+        reverse: pressed(1),
+        left: pressed(2),
+        right: pressed(3),
+        fire: pressed(4),
+        hyperspace: pressed(5),
+        // Agents only change weapons by picking them up
+        switch_weapon: false,
+    })
+}
+
+fn env_config(
+    frame_skip: u32,
+    max_steps: Option<u32>,
+    points_reward: f32,
+    life_lost_reward: f32,
+    survival_reward: f32,
+) -> EnvConfig {
+    EnvConfig {
+        frame_skip,
+        max_steps,
+        reward: RewardConfig {
+            points: points_reward,
+            life_lost: life_lost_reward,
+            survival: survival_reward,
+        },
+    }
+}
+
+// One single-player game
+#[pyclass(name = "Env")]
+struct PyEnv {
+    env: Env,
+    max_asteroids: usize,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (
+        frame_skip = 4,
+        max_steps = None,
+        points_reward = 1.0,
+        life_lost_reward = -100.0,
+        survival_reward = 0.0,
+        max_asteroids = 8,
+    ))]
+    fn new(
+        py: Python,
+        frame_skip: u32,
+        max_steps: Option<u32>,
+        points_reward: f32,
+        life_lost_reward: f32,
+        survival_reward: f32,
+        max_asteroids: usize,
+    ) -> Self {
+        let config = env_config(
+            frame_skip,
+            max_steps,
+            points_reward,
+            life_lost_reward,
+            survival_reward,
+        );
+
+        PyEnv {
+            env: py.allow_threads(|| Env::new(config)),
+            max_asteroids,
+        }
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        observation_size(self.max_asteroids)
+    }
+
+    #[getter]
+    fn action_size(&self) -> usize {
+        ACTION_SIZE
+    }
+
+    // Starts a new game and returns its first observation
+    #[pyo3(signature = (seed = 0))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> Bound<'py, PyArray1<f32>> {
+        let observation = py.allow_threads(|| self.env.reset(seed));
+        features(&observation, self.max_asteroids).into_pyarray(py)
+    }
+
+    // Returns `(observation, reward, done)`
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: Vec<f32>,
+    ) -> PyResult<(Bound<'py, PyArray1<f32>>, f32, bool)> {
+        let input = self::action(&action)?;
+        let Step {
+            observation,
+            reward,
+            done,
+        } = py.allow_threads(|| self.env.step(input));
+
+        Ok((
+            features(&observation, self.max_asteroids).into_pyarray(py),
+            reward,
+            done,
+        ))
+    }
+
+    fn observation<'py>(&mut self, py: Python<'py>) -> Bound<'py, PyArray1<f32>> {
+        features(&self.env.observation(), self.max_asteroids).into_pyarray(py)
+    }
+
+    fn is_over(&self) -> bool {
+        self.env.is_over()
+    }
+}
+
+// Many games stepped together. Observations are stacked into one
+// `(len, observation_size)` array, one row per game.
+#[pyclass(name = "VecEnv")]
+struct PyVecEnv {
+    envs: VecEnv,
+    max_asteroids: usize,
+}
+
+impl PyVecEnv {
+    fn stack(&self, observations: &[Observation]) -> Array2<f32> {
+        let size = observation_size(self.max_asteroids);
+        let rows = observations
+            .iter()
+            .flat_map(|observation| features(observation, self.max_asteroids))
+            .collect();
+
+        Array2::from_shape_vec((observations.len(), size), rows).unwrap()
+    }
+}
+
+#[pymethods]
+impl PyVecEnv {
+    #[new]
+    #[pyo3(signature = (
+        num_envs,
+        threads = None,
+        frame_skip = 4,
+        max_steps = None,
+        points_reward = 1.0,
+        life_lost_reward = -100.0,
+        survival_reward = 0.0,
+        max_asteroids = 8,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        num_envs: usize,
+        threads: Option<usize>,
+        frame_skip: u32,
+        max_steps: Option<u32>,
+        points_reward: f32,
+        life_lost_reward: f32,
+        survival_reward: f32,
+        max_asteroids: usize,
+    ) -> Self {
+        let config = env_config(
+            frame_skip,
+            max_steps,
+            points_reward,
+            life_lost_reward,
+            survival_reward,
+        );
+        // One thread per core unless told otherwise
+        let threads = threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |threads| threads.get())
+        });
+
+        PyVecEnv {
+            envs: py.allow_threads(|| VecEnv::new(num_envs, config, threads)),
+            max_asteroids,
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.envs.len()
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        observation_size(self.max_asteroids)
+    }
+
+    #[getter]
+    fn action_size(&self) -> usize {
+        ACTION_SIZE
+    }
+
+    // Starts a new game in every environment, each from its own seed
+    fn reset<'py>(
+        &mut self,
+        py: Python<'py>,
+        seeds: Vec<u64>,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        if seeds.len() != self.envs.len() {
+            return Err(PyValueError::new_err(format!(
+                "expected {} seeds, got {}",
+                self.envs.len(),
+                seeds.len()
+            )));
+        }
+
+        let observations = py.allow_threads(|| self.envs.reset(&seeds));
+        Ok(self.stack(&observations).into_pyarray(py))
+    }
+
+    // Starts a new game in one environment, e.g. once its episode is done
+    fn reset_one<'py>(
+        &mut self,
+        py: Python<'py>,
+        index: usize,
+        seed: u64,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        if index >= self.envs.len() {
+            return Err(PyValueError::new_err(format!(
+                "no environment {}, there are {}",
+                index,
+                self.envs.len()
+            )));
+        }
+
+        let observation = py.allow_threads(|| self.envs.reset_one(index, seed));
+        Ok(features(&observation, self.max_asteroids).into_pyarray(py))
+    }
+
+    // Takes one action row per environment and returns `(observations,
+    // rewards, dones)`. Environments that are done stay done until reset.
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        actions: Vec<Vec<f32>>,
+    ) -> PyResult<(
+        Bound<'py, PyArray2<f32>>,
+        Bound<'py, PyArray1<f32>>,
+        Bound<'py, PyArray1<bool>>,
+    )> {
+        if actions.len() != self.envs.len() {
+            return Err(PyValueError::new_err(format!(
+                "expected {} actions, got {}",
+                self.envs.len(),
+                actions.len()
+            )));
+        }
+        let inputs = actions
+            .iter()
+            .map(|values| action(values))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let steps = py.allow_threads(|| self.envs.step(&inputs));
+
+        let observations: Vec<Observation> =
+            steps.iter().map(|step| step.observation.clone()).collect();
+        let rewards: Vec<f32> = steps.iter().map(|step| step.reward).collect();
+        let dones: Vec<bool> = steps.iter().map(|step| step.done).collect();
+
+        Ok((
+            self.stack(&observations).into_pyarray(py),
+            rewards.into_pyarray(py),
+            dones.into_pyarray(py),
+        ))
+    }
+}
+
+#[pymodule]
+fn _native(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEnv>()?;
+    module.add_class::<PyVecEnv>()?;
+    module.add("ACTION_SIZE", ACTION_SIZE)?;
+    module.add("SHIP_FEATURES", SHIP_FEATURES)?;
+    module.add("ASTEROID_FEATURES", ASTEROID_FEATURES)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// The title screen's attract loop. After a while on the menu with nobody
+// touching the controls, a demo game flown by the built-in bot plays behind the
+// menu text. Any key or button brings the plain title back.
+
+use std::time::Duration;
+
+use crate::controls::{Bots, ControlsPlugin};
+use crate::game::{
+    headless_game, mirror_snapshot, GameAssets, GameSnapshot, MirrorSprite, Roster,
+    FIXED_FRAME_RATE,
+};
+use crate::{despawn_screen, GameState};
+use bevy::prelude::*;
+
+// How long the title sits idle before the demo starts
+const IDLE_SECONDS: f32 = 10.0;
+
+// Demos go back to the title after this long, even if the bot is still alive
+const DEMO_SECONDS: f32 = 60.0;
+
+// At most this many demo frames are played per frame drawn, so a slow machine
+// falls behind instead of grinding to a halt
+const MAX_STEPS: usize = 4;
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut App) {
+        // The demo is a whole bevy app, which can't be shared between threads
+        app.insert_non_send_resource(Attract {
+            idle: Timer::from_seconds(IDLE_SECONDS, false),
+            demo: None,
+            demo_timer: Timer::from_seconds(DEMO_SECONDS, false),
+            owed: Duration::ZERO,
+        })
+        .init_resource::<GameAssets>()
+        .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(reset_attract))
+        .add_system_set(SystemSet::on_update(GameState::Menu).with_system(attract_loop))
+        .add_system_set(
+            SystemSet::on_exit(GameState::Menu)
+                .with_system(reset_attract)
+                .with_system(despawn_screen::<DemoSprite>),
+        );
+    }
+}
+
+struct Attract {
+    idle: Timer,
+    // The demo game, while it's playing
+    demo: Option<App>,
+    demo_timer: Timer,
+    // Demo time that should have been played by now but hasn't yet
+    owed: Duration,
+}
+
+#[derive(Component, Clone)]
+struct DemoSprite;
+
+// A headless single-player game with the bot at the controls
+fn demo_game(seed: u64) -> App {
+    let mut app = headless_game(seed, Roster::default());
+    app.insert_resource(Bots(vec![0]))
+        .add_plugin(ControlsPlugin);
+
+    app.update();
+    app
+}
+
+fn reset_attract(mut attract: NonSendMut<Attract>) {
+    attract.idle.reset();
+    attract.demo = None;
+}
+
+fn attract_loop(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut attract: NonSendMut<Attract>,
+    sprite_query: Query<(Entity, &MirrorSprite), With<DemoSprite>>,
+) {
+    let touched =
+        keys.get_just_pressed().next().is_some() || buttons.get_just_pressed().next().is_some();
+    let Attract {
+        idle,
+        demo,
+        demo_timer,
+        owed,
+    } = &mut *attract;
+
+    let finished = match demo {
+        Some(_) if touched => true,
+        Some(app) => {
+            // The demo plays at a steady 60 frames a second whatever the
+            // refresh rate
+            *owed += time.delta();
+            let frame_time = Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE);
+
+            let mut steps = 0;
+            while *owed >= frame_time && steps < MAX_STEPS {
+                *owed -= frame_time;
+                app.update();
+                steps += 1;
+            }
+            if steps == MAX_STEPS {
+                *owed = Duration::ZERO;
+            }
+
+            let snapshot = GameSnapshot::save(&mut app.world);
+
+            if snapshot.over || demo_timer.tick(time.delta()).finished() {
+                true
+            } else {
+                mirror_snapshot(
+                    &mut commands,
+                    &assets,
+                    &snapshot,
+                    sprite_query
+                        .iter()
+                        .map(|(entity, sprite)| (entity, *sprite)),
+                    DemoSprite,
+                );
+                false
+            }
+        }
+        None => {
+            if touched {
+                idle.reset();
+            } else if idle.tick(time.delta()).finished() {
+                *demo = Some(demo_game(rand::random()));
+                demo_timer.reset();
+                *owed = Duration::ZERO;
+            }
+            false
+        }
+    };
+
+    if finished {
+        *demo = None;
+        idle.reset();
+
+        for (entity, _) in sprite_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// Leaderboard server for tournaments, see `leaderboard.rs` for what it serves.
+// Every submission is played again from its replay before it's stored, in an
+// SQLite database along with the replay itself.
+//
+//     asteroids-leaderboard [--bind 127.0.0.1:8080] [--database leaderboard.db] [--key SECRET]
+//
+// It's only built with the `leaderboard-server` feature:
+//
+//     cargo run --release --features leaderboard-server --bin asteroids-leaderboard
+//
+// With a key, only submissions signed with the same key are taken. Point the
+// game at the server with `"leaderboard_url": "http://127.0.0.1:8080"` in
+// its settings file, and `"leaderboard_key"` if there's a key.
+//
+// Requests are handled one at a time, which is plenty for a room full of
+// players but means a long replay holds up the ones behind it.
+
+use std::io::Read;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use asteroids::game::GameMode;
+use asteroids::leaderboard::{
+    signature_matches, LeaderboardEntry, SubmitReply, DEFAULT_PORT, NAME_HEADER, SIGNATURE_HEADER,
+    TABLE_SIZE,
+};
+use asteroids::replay::Replay;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Far more than any game's replay
+const MAX_BODY_BYTES: u64 = 32 * 1024 * 1024;
+
+const MAX_NAME_LENGTH: usize = 16;
+
+struct Options {
+    bind: SocketAddr,
+    database: String,
+    key: Option<String>,
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        bind: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+        database: "leaderboard.db".into(),
+        key: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bind" => {
+                options.bind = args
+                    .next()
+                    .and_then(|addr| addr.parse().ok())
+                    .expect("--bind needs an address like 127.0.0.1:8080")
+            }
+            "--database" => options.database = args.next().expect("--database needs a file name"),
+            "--key" => options.key = Some(args.next().expect("--key needs a key")),
+            _ => {
+                eprintln!(
+                    "usage: asteroids-leaderboard [--bind ADDRESS] [--database FILE] [--key KEY]"
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    options
+}
+
+fn open_database(path: &str) -> rusqlite::Result<Connection> {
+    let database = Connection::open(path)?;
+    database.execute_batch(
+        "CREATE TABLE IF NOT EXISTS scores (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            mode TEXT NOT NULL,
+            seed INTEGER NOT NULL,
+            difficulty TEXT NOT NULL,
+            players INTEGER NOT NULL,
+            points INTEGER NOT NULL,
+            seconds REAL NOT NULL,
+            wave INTEGER NOT NULL,
+            submitted_at INTEGER NOT NULL,
+            -- So the same game can't be entered twice
+            replay_hash TEXT NOT NULL UNIQUE,
+            replay BLOB NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS scores_by_mode ON scores (mode, seed);",
+    )?;
+
+    Ok(database)
+}
+
+fn main() {
+    let options = parse_options();
+
+    let database = open_database(&options.database).unwrap_or_else(|error| {
+        eprintln!("can't open {}: {}", options.database, error);
+        std::process::exit(1);
+    });
+    let server = Server::http(options.bind).unwrap_or_else(|error| {
+        eprintln!("can't listen on {}: {}", options.bind, error);
+        std::process::exit(1);
+    });
+    println!("leaderboard listening on http://{}", options.bind);
+
+    for request in server.incoming_requests() {
+        handle(request, &database, options.key.as_deref());
+    }
+}
+
+fn handle(mut request: Request, database: &Connection, key: Option<&str>) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    let (status, body) = match (request.method(), path) {
+        (Method::Post, "/scores") => {
+            let (status, reply) = submit(&mut request, database, key);
+            (status, json(&reply))
+        }
+        (Method::Get, "/scores") => match scores(database, query) {
+            Ok(entries) => (200, json(&entries)),
+            Err(error) => (400, json(&error)),
+        },
+        _ => (404, json(&"no such page")),
+    };
+
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap());
+    if let Err(error) = request.respond(response) {
+        eprintln!("failed to answer {}: {}", url, error);
+    }
+}
+
+fn json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("failed to encode reply")
+}
+
+fn header(request: &Request, name: &str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|header| header.value.as_str().to_string())
+}
+
+fn rejected(error: impl Into<String>) -> SubmitReply {
+    SubmitReply {
+        rank: None,
+        errors: vec![error.into()],
+    }
+}
+
+fn submit(request: &mut Request, database: &Connection, key: Option<&str>) -> (u16, SubmitReply) {
+    let mut body = Vec::new();
+    if let Err(error) = request
+        .as_reader()
+        .take(MAX_BODY_BYTES)
+        .read_to_end(&mut body)
+    {
+        return (400, rejected(error.to_string()));
+    }
+
+    if let Some(key) = key {
+        let signed = header(request, SIGNATURE_HEADER)
+            .is_some_and(|signature| signature_matches(key, &body, &signature));
+        if !signed {
+            return (
+                401,
+                rejected("the submission isn't signed with this server's key"),
+            );
+        }
+    }
+
+    let replay = match Replay::from_bytes(&body) {
+        Ok(replay) => replay,
+        Err(error) => return (400, rejected(format!("not a replay: {}", error))),
+    };
+    if replay.roster.versus {
+        return (400, rejected("versus games aren't ranked"));
+    }
+
+    // The same run recorded at another time, or claiming another ending, is
+    // still the same run
+    let hash = match replay.run_bytes() {
+        Ok(run) => hex::encode(Sha256::digest(&run)),
+        Err(error) => return (400, rejected(format!("not a replay: {}", error))),
+    };
+    let known = database
+        .query_row(
+            "SELECT 1 FROM scores WHERE replay_hash = ?1",
+            params![hash],
+            |_| Ok(()),
+        )
+        .optional();
+    match known {
+        Ok(Some(())) => return (409, rejected("this game has already been entered")),
+        Ok(None) => {}
+        Err(error) => return (500, rejected(error.to_string())),
+    }
+
+    let stats = match replay.verify() {
+        Ok(stats) => stats,
+        Err(mismatches) => {
+            println!(
+                "rejected a {} game: {}",
+                replay.mode.key(),
+                mismatches.join("; ")
+            );
+            return (
+                422,
+                SubmitReply {
+                    rank: None,
+                    errors: mismatches,
+                },
+            );
+        }
+    };
+
+    let name = header(request, NAME_HEADER)
+        .map(|name| {
+            name.trim()
+                .chars()
+                .take(MAX_NAME_LENGTH)
+                .collect::<String>()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "ANONYMOUS".into());
+    let entry = LeaderboardEntry {
+        name,
+        points: replay.points(),
+        seconds: stats
+            .players
+            .iter()
+            .map(|stats| stats.time_survived.as_secs_f32())
+            .fold(0.0, f32::max),
+        wave: stats.wave,
+        players: replay.roster.players,
+        seed: replay.seed,
+    };
+
+    match store(database, &replay, &entry, &hash, &body) {
+        Ok(rank) => {
+            println!(
+                "{} scored {} in {}, rank {}",
+                entry.name,
+                entry.points,
+                replay.mode.key(),
+                rank
+            );
+            (
+                200,
+                SubmitReply {
+                    rank: Some(rank),
+                    errors: Vec::new(),
+                },
+            )
+        }
+        Err(error) => (500, rejected(error.to_string())),
+    }
+}
+
+// Orders a mode's entries best first, the way the game's own high score
+// tables do: survival runs by how long they lasted, the rest by points
+fn ranking(mode: GameMode) -> &'static str {
+    if mode == GameMode::Survival {
+        "seconds DESC, points DESC"
+    } else {
+        "points DESC, seconds DESC"
+    }
+}
+
+// Stores an entry and returns its place in its mode's table, counting from one
+fn store(
+    database: &Connection,
+    replay: &Replay,
+    entry: &LeaderboardEntry,
+    hash: &str,
+    body: &[u8],
+) -> rusqlite::Result<usize> {
+    let submitted_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+
+    database.execute(
+        "INSERT INTO scores (name, mode, seed, difficulty, players, points, seconds, wave,
+            submitted_at, replay_hash, replay)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            entry.name,
+            replay.mode.key(),
+            entry.seed as i64,
+            format!("{:?}", replay.difficulty),
+            entry.players as i64,
+            entry.points,
+            entry.seconds,
+            entry.wave,
+            submitted_at as i64,
+            hash,
+            body
+        ],
+    )?;
+
+    // Anything tied with the new entry got there first, so stays ahead of it
+    let id = database.last_insert_rowid();
+    let mut statement = database.prepare(&format!(
+        "SELECT id FROM scores WHERE mode = ?1 ORDER BY {}, id",
+        ranking(replay.mode)
+    ))?;
+    let ids = statement
+        .query_map(params![replay.mode.key()], |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(ids
+        .iter()
+        .position(|&other| other == id)
+        .unwrap_or(ids.len())
+        + 1)
+}
+
+// `GET /scores?mode=...&seed=...`
+fn scores(database: &Connection, query: &str) -> Result<Vec<LeaderboardEntry>, String> {
+    let mut mode = None;
+    let mut seed = None;
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        match pair.split_once('=') {
+            Some(("mode", value)) => mode = GameMode::from_key(value),
+            Some(("seed", value)) => {
+                seed = Some(value.parse::<u64>().map_err(|_| "seed must be a number")?)
+            }
+            _ => return Err(format!("unknown query {}", pair)),
+        }
+    }
+    let mode = mode.ok_or("mode must be one of classic, time-attack, survival or zen")?;
+
+    let mut statement = database
+        .prepare(&format!(
+            "SELECT name, points, seconds, wave, players, seed FROM scores
+            WHERE mode = ?1 AND (?2 IS NULL OR seed = ?2)
+            ORDER BY {} LIMIT ?3",
+            ranking(mode)
+        ))
+        .map_err(|error| error.to_string())?;
+
+    let entries = statement
+        .query_map(
+            params![mode.key(), seed.map(|seed| seed as i64), TABLE_SIZE as i64],
+            |row| {
+                Ok(LeaderboardEntry {
+                    name: row.get(0)?,
+                    points: row.get(1)?,
+                    seconds: row.get(2)?,
+                    wave: row.get(3)?,
+                    players: row.get::<_, i64>(4)? as usize,
+                    seed: row.get::<_, i64>(5)? as u64,
+                })
+            },
+        )
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| error.to_string())?;
+
+    Ok(entries)
+}
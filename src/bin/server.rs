@@ -0,0 +1,59 @@
+// Dedicated server for networked games. Runs the game headless and waits for
+// clients started with `asteroids --connect <address>`.
+//
+//     asteroids-server [--bind 0.0.0.0:7777] [--players 2] [--versus]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use asteroids::game::{GamePlugin, Roster};
+use asteroids::net::server::NetServerPlugin;
+use asteroids::net::DEFAULT_PORT;
+use asteroids::GameState;
+use bevy::app::ScheduleRunnerSettings;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+
+fn main() {
+    let mut bind = SocketAddr::from(([0, 0, 0, 0], DEFAULT_PORT));
+    let mut roster = Roster {
+        players: 2,
+        versus: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bind" => {
+                bind = args
+                    .next()
+                    .and_then(|addr| addr.parse().ok())
+                    .expect("--bind needs an address like 127.0.0.1:7777")
+            }
+            "--players" => {
+                roster.players = args
+                    .next()
+                    .and_then(|players| players.parse().ok())
+                    .filter(|players| (1..=4).contains(players))
+                    .expect("--players needs a number from 1 to 4")
+            }
+            "--versus" => roster.versus = true,
+            _ => {
+                eprintln!("usage: asteroids-server [--bind ADDRESS] [--players N] [--versus]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    App::new()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin)
+        .insert_resource(roster)
+        .add_plugin(GamePlugin)
+        .add_plugin(NetServerPlugin { bind })
+        .add_state(GameState::Menu)
+        .run();
+}
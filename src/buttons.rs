@@ -0,0 +1,174 @@
+// Focusable lists of buttons for the menu screens. One button in the list has
+// the focus, which the keyboard (up/down or W/S), any gamepad's d-pad and the
+// mouse pointer move around. Enter, space, the gamepad's A button or a click
+// picks the focused button and sends its action as an event, so a screen only
+// spawns its buttons and reads back what was picked.
+
+use crate::GameState;
+use bevy::app::Events;
+use bevy::prelude::*;
+
+const FOCUSED_BUTTON: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+const FOCUSED_TEXT: Color = Color::WHITE;
+const BUTTON_TEXT: Color = Color::GRAY;
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct NavigateButtons;
+
+// Lets buttons with actions of type `A` be used while in `state`. Picked
+// actions arrive as `A` events after `NavigateButtons`.
+pub fn add_button_list<A: Copy + Send + Sync + 'static>(app: &mut App, state: GameState) {
+    // Screens can share an action type, but each event type must only be
+    // added once or its events are cleared twice a frame
+    if !app.world.contains_resource::<Events<A>>() {
+        app.add_event::<A>();
+    }
+
+    app.init_resource::<ButtonFocus>()
+        .add_system_set(SystemSet::on_enter(state.clone()).with_system(reset_focus))
+        .add_system_set(
+            SystemSet::on_update(state)
+                .with_system(navigate_buttons::<A>.label(NavigateButtons))
+                .with_system(highlight_buttons::<A>.after(NavigateButtons)),
+        );
+}
+
+// The position of the focused button in the current screen's list
+#[derive(Default)]
+struct ButtonFocus {
+    index: usize,
+    // A new screen's first frame is the one that left the last screen, so
+    // the key or button that did that isn't let through again
+    ready: bool,
+}
+
+#[derive(Component)]
+pub struct MenuButton<A: Send + Sync + 'static> {
+    index: usize,
+    action: A,
+}
+
+impl<A: Copy + Send + Sync + 'static> MenuButton<A> {
+    pub fn action(&self) -> A {
+        self.action
+    }
+}
+
+// Spawns a column of buttons, focusable from top to bottom
+pub fn spawn_buttons<A: Copy + Send + Sync + 'static>(
+    parent: &mut ChildBuilder,
+    font: &Handle<Font>,
+    buttons: &[(&str, A)],
+) {
+    for (index, (label, action)) in buttons.iter().enumerate() {
+        parent
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    // Long labels widen the button
+                    size: Size::new(Val::Auto, Val::Px(52.0)),
+                    min_size: Size::new(Val::Px(400.0), Val::Auto),
+                    margin: Rect::all(Val::Px(4.0)),
+                    padding: Rect {
+                        left: Val::Px(20.0),
+                        right: Val::Px(20.0),
+                        ..Default::default()
+                    },
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: Color::NONE.into(),
+                ..Default::default()
+            })
+            .insert(MenuButton {
+                index,
+                action: *action,
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        *label,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 32.0,
+                            color: BUTTON_TEXT,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+fn reset_focus(mut focus: ResMut<ButtonFocus>) {
+    *focus = ButtonFocus::default();
+}
+
+fn navigate_buttons<A: Copy + Send + Sync + 'static>(
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut focus: ResMut<ButtonFocus>,
+    mut picked: EventWriter<A>,
+    buttons: Query<&MenuButton<A>>,
+    interactions: Query<(&Interaction, &MenuButton<A>), Changed<Interaction>>,
+) {
+    let count = buttons.iter().count();
+    if count == 0 || !focus.ready {
+        focus.ready = count > 0;
+        return;
+    }
+
+    let key = |codes: &[KeyCode]| codes.iter().any(|code| keys.just_pressed(*code));
+    let gamepad = |button_type| {
+        gamepad_buttons
+            .get_just_pressed()
+            .any(|button| button.1 == button_type)
+    };
+
+    if key(&[KeyCode::Up, KeyCode::W]) || gamepad(GamepadButtonType::DPadUp) {
+        focus.index = (focus.index + count - 1) % count;
+    } else if key(&[KeyCode::Down, KeyCode::S]) || gamepad(GamepadButtonType::DPadDown) {
+        focus.index = (focus.index + 1) % count;
+    }
+
+    let mut pick = keys.just_released(KeyCode::Return)
+        || keys.just_released(KeyCode::Space)
+        || gamepad_buttons
+            .get_just_released()
+            .any(|button| button.1 == GamepadButtonType::South);
+
+    for (interaction, button) in interactions.iter() {
+        match interaction {
+            Interaction::Hovered => focus.index = button.index,
+            Interaction::Clicked => {
+                focus.index = button.index;
+                pick = true;
+            }
+            Interaction::None => {}
+        }
+    }
+
+    if pick {
+        if let Some(button) = buttons.iter().find(|button| button.index == focus.index) {
+            picked.send(button.action);
+        }
+    }
+}
+
+fn highlight_buttons<A: Copy + Send + Sync + 'static>(
+    focus: Res<ButtonFocus>,
+    mut buttons: Query<(&MenuButton<A>, &mut UiColor, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, mut color, children) in buttons.iter_mut() {
+        let focused = button.index == focus.index;
+        *color = if focused { FOCUSED_BUTTON } else { Color::NONE }.into();
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].style.color = if focused { FOCUSED_TEXT } else { BUTTON_TEXT };
+            }
+        }
+    }
+}
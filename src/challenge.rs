@@ -0,0 +1,138 @@
+// Games that can be shared. A seed code pins down everything that decides the
+// asteroid field: the seed, the mode and the difficulty. Anyone who enters the
+// code plays the same run. Codes are nine characters from Crockford's base 32,
+// which leaves out letters easily mistaken for digits, grouped in threes:
+//
+//     7XK-3MQ-2ZD
+//
+// The daily challenge is a code everyone gets on the same day, made from the
+// date.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game::{Difficulty, GameMode, GAME_MODES, SEED_BITS};
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+const CODE_LENGTH: usize = 9;
+
+// Bits after the seed in a code
+const MODE_BITS: u32 = 3;
+const DIFFICULTY_BITS: u32 = 2;
+
+const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+// Daily challenges go round these, a day each. Zen runs never end, so they
+// wouldn't make much of a race.
+const DAILY_MODES: [GameMode; 3] = [GameMode::Classic, GameMode::TimeAttack, GameMode::Survival];
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Challenge {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+}
+
+impl Challenge {
+    pub fn code(&self) -> String {
+        let mode = self.mode as u64;
+        let difficulty = self.difficulty as u64;
+        let mut value =
+            (self.seed << (MODE_BITS + DIFFICULTY_BITS)) | (mode << DIFFICULTY_BITS) | difficulty;
+
+        let mut digits = [0; CODE_LENGTH];
+        for digit in digits.iter_mut().rev() {
+            *digit = ALPHABET[(value & 31) as usize];
+            value >>= 5;
+        }
+
+        digits
+            .chunks(3)
+            .map(|chunk| String::from_utf8_lossy(chunk))
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    // Reads a code back, forgiving case, dashes and spaces and the letters
+    // Crockford's alphabet leaves out for looking like digits
+    pub fn from_code(code: &str) -> Option<Challenge> {
+        let mut value = 0u64;
+        let mut length = 0;
+
+        for character in code.chars().filter(|c| !matches!(c, '-' | ' ')) {
+            let character = match character.to_ascii_uppercase() {
+                'O' => '0',
+                'I' | 'L' => '1',
+                other => other,
+            };
+            let digit = ALPHABET.iter().position(|&c| c as char == character)?;
+
+            value = (value << 5) | digit as u64;
+            length += 1;
+        }
+
+        if length != CODE_LENGTH {
+            return None;
+        }
+
+        let difficulty = value & ((1 << DIFFICULTY_BITS) - 1);
+        let mode = (value >> DIFFICULTY_BITS) & ((1 << MODE_BITS) - 1);
+        let seed = value >> (MODE_BITS + DIFFICULTY_BITS);
+
+        Some(Challenge {
+            seed,
+            mode: *GAME_MODES.get(mode as usize)?,
+            difficulty: *DIFFICULTIES.get(difficulty as usize)?,
+        })
+    }
+
+    // The challenge for a day, counted from the start of 1970 in UTC
+    pub fn daily(day: u64) -> Challenge {
+        let mode = DAILY_MODES[(day % DAILY_MODES.len() as u64) as usize];
+
+        Challenge {
+            seed: mix(day ^ ((mode as u64) << 32)) >> (64 - SEED_BITS),
+            mode,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+// SplitMix64's finaliser, which spreads neighbouring days far apart. Unlike
+// the standard library's hashers it's fixed, so a day's seed never changes.
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+// Today, as a day number for `Challenge::daily`
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() / SECONDS_PER_DAY)
+}
+
+// A day number as a date, e.g. 2026-10-18
+pub fn date_text(day: u64) -> String {
+    // Howard Hinnant's `civil_from_days`, for days since 1970
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{}-{:02}-{:02}", year, month, day_of_month)
+}
@@ -0,0 +1,241 @@
+use crate::game::{GameMode, GameSystem, Player, Roster};
+use crate::pilot::{HeuristicBot, Observation, Pilot, ShipController};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Gives the ships a pilot on this machine: its keyboard and gamepads, or the
+// built-in bot for players listed in `Bots`. Left out when ships are driven
+// from elsewhere, e.g. on a dedicated server.
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bots>()
+            .init_resource::<ControlScheme>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(
+                        attach_controls
+                            .label("attach_controls")
+                            .before(GameSystem::Input),
+                    )
+                    .with_system(leave_zen),
+            );
+    }
+}
+
+// Players flown by `HeuristicBot` instead of a person, by player id
+#[derive(Default)]
+pub struct Bots(pub Vec<usize>);
+
+// Keyboard layout for flying one ship
+#[derive(Clone, Copy)]
+pub struct KeySet {
+    pub thrust: KeyCode,
+    pub reverse: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub fire: KeyCode,
+    pub hyperspace: KeyCode,
+    pub switch_weapon: KeyCode,
+}
+
+// Two players can share one keyboard, one on each side
+pub const KEY_SETS: [KeySet; 2] = [
+    KeySet {
+        thrust: KeyCode::W,
+        reverse: KeyCode::S,
+        left: KeyCode::A,
+        right: KeyCode::D,
+        fire: KeyCode::J,
+        hyperspace: KeyCode::K,
+        switch_weapon: KeyCode::L,
+    },
+    KeySet {
+        thrust: KeyCode::Up,
+        reverse: KeyCode::Down,
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        fire: KeyCode::RShift,
+        hyperspace: KeyCode::RControl,
+        switch_weapon: KeyCode::Slash,
+    },
+];
+
+// Which side of the keyboard each player gets
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ControlScheme {
+    // Player 1 on WASD, player 2 on the arrow keys
+    #[default]
+    Standard,
+    // Player 1 on the arrow keys, player 2 on WASD
+    Swapped,
+}
+
+impl ControlScheme {
+    pub fn key_set(self, player: usize) -> Option<KeySet> {
+        let index = match (self, player) {
+            (ControlScheme::Swapped, 0) => 1,
+            (ControlScheme::Swapped, 1) => 0,
+            _ => player,
+        };
+        KEY_SETS.get(index).copied()
+    }
+}
+
+// How far a stick has to be pushed before it counts as pressed
+const STICK_THRESHOLD: f32 = 0.5;
+
+// The inputs that steer a ship. Every ship also listens to the gamepad with
+// the same index as its player, if one is plugged in.
+#[derive(Component, Clone, Copy)]
+pub struct Controls {
+    pub keys: Option<KeySet>,
+    pub gamepad: Gamepad,
+}
+
+impl Controls {
+    pub fn for_player(id: usize, scheme: ControlScheme) -> Self {
+        Controls {
+            keys: scheme.key_set(id),
+            gamepad: Gamepad(id),
+        }
+    }
+}
+
+// The action a ship's pilot takes this frame. Ships only ever move on this,
+// whether it came from a person, a bot or the network. Buttons missing when
+// it's read back in aren't pressed.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShipInput {
+    pub thrust: bool,
+    pub reverse: bool,
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+    pub hyperspace: bool,
+    // Moves on to the ship's next weapon
+    pub switch_weapon: bool,
+}
+
+// This machine's input devices, where it has them. A headless game has none.
+#[derive(Clone, Copy, Default)]
+pub struct Devices<'a> {
+    pub keys: Option<&'a Input<KeyCode>>,
+    pub buttons: Option<&'a Input<GamepadButton>>,
+    pub axes: Option<&'a Axis<GamepadAxis>>,
+}
+
+impl Controls {
+    pub fn read(&self, devices: &Devices) -> ShipInput {
+        let button = |button_type| {
+            devices
+                .buttons
+                .is_some_and(|buttons| buttons.pressed(GamepadButton(self.gamepad, button_type)))
+        };
+        let axis = |axis_type| {
+            devices
+                .axes
+                .and_then(|axes| axes.get(GamepadAxis(self.gamepad, axis_type)))
+                .unwrap_or(0.0)
+        };
+        let key = |select: fn(&KeySet) -> KeyCode| match (self.keys, devices.keys) {
+            (Some(key_set), Some(keys)) => keys.pressed(select(&key_set)),
+            _ => false,
+        };
+
+        ShipInput {
+            thrust: key(|k| k.thrust)
+                || button(GamepadButtonType::DPadUp)
+                || button(GamepadButtonType::RightTrigger2)
+                || axis(GamepadAxisType::LeftStickY) > STICK_THRESHOLD,
+            reverse: key(|k| k.reverse)
+                || button(GamepadButtonType::DPadDown)
+                || button(GamepadButtonType::LeftTrigger2)
+                || axis(GamepadAxisType::LeftStickY) < -STICK_THRESHOLD,
+            left: key(|k| k.left)
+                || button(GamepadButtonType::DPadLeft)
+                || axis(GamepadAxisType::LeftStickX) < -STICK_THRESHOLD,
+            right: key(|k| k.right)
+                || button(GamepadButtonType::DPadRight)
+                || axis(GamepadAxisType::LeftStickX) > STICK_THRESHOLD,
+            fire: key(|k| k.fire) || button(GamepadButtonType::South),
+            hyperspace: key(|k| k.hyperspace) || button(GamepadButtonType::East),
+            switch_weapon: key(|k| k.switch_weapon) || button(GamepadButtonType::North),
+        }
+    }
+}
+
+// The keyboard is just another pilot, one that ignores what it sees
+impl ShipController for Controls {
+    fn act(&mut self, _observation: &Observation, devices: &Devices) -> ShipInput {
+        self.read(devices)
+    }
+}
+
+// Gives each new ship a pilot, either the bot or the keys and gamepad for its player
+fn attach_controls(
+    mut commands: Commands,
+    bots: Res<Bots>,
+    scheme: Res<ControlScheme>,
+    query: Query<(Entity, &Player), Without<Pilot>>,
+) {
+    for (entity, player) in query.iter() {
+        let pilot = if bots.0.contains(&player.id) {
+            Pilot::new(HeuristicBot)
+        } else {
+            Pilot::new(Controls::for_player(player.id, *scheme))
+        };
+
+        commands.entity(entity).insert(pilot);
+    }
+}
+
+// Zen games only end when the players have had enough, on escape or a
+// gamepad's select button. The press is used up here, so the game over screen
+// doesn't take it too. Demo games have no devices to read.
+fn leave_zen(
+    keys: Option<ResMut<Input<KeyCode>>>,
+    buttons: Option<ResMut<Input<GamepadButton>>>,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if roster.versus || *mode != GameMode::Zen {
+        return;
+    }
+
+    let key = keys.is_some_and(|mut keys| keys.clear_just_released(KeyCode::Escape));
+    let button = buttons.is_some_and(|mut buttons| {
+        let select = buttons
+            .get_just_released()
+            .find(|button| button.1 == GamepadButtonType::Select)
+            .copied();
+        select.is_some_and(|select| buttons.clear_just_released(select))
+    });
+
+    if key || button {
+        let _ = game_state.set(GameState::GameOver);
+    }
+}
+
+// Fills in `ShipInput` straight from the local controls, for ships drawn from
+// somewhere else, e.g. a network game, where there's nothing to observe
+pub fn read_controls(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut query: Query<(&Controls, &mut ShipInput)>,
+) {
+    let devices = Devices {
+        keys: Some(&keys),
+        buttons: Some(&buttons),
+        axes: Some(&axes),
+    };
+
+    for (controls, mut input) in query.iter_mut() {
+        *input = controls.read(&devices);
+    }
+}
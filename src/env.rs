@@ -0,0 +1,245 @@
+// A reinforcement-learning environment in the style of OpenAI Gym. Each
+// environment plays its own headless single-player game: `step` takes the
+// ship's action and hands back what the ship sees, the reward for the step and
+// whether the episode is over.
+
+use crate::controls::ShipInput;
+use crate::game::{headless_game, Player, Roster, Scoreboard, FIXED_FRAME_RATE};
+use crate::pilot::{observe_world, Observation};
+use crate::GameState;
+use bevy::ecs::schedule::Schedule;
+use bevy::prelude::*;
+
+// What earns reward. Each part can be switched off by setting it to zero.
+#[derive(Clone)]
+pub struct RewardConfig {
+    // Per point scored
+    pub points: f32,
+    // Per life lost, normally negative
+    pub life_lost: f32,
+    // Per second the ship stays in play
+    pub survival: f32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        RewardConfig {
+            points: 1.0,
+            life_lost: -100.0,
+            survival: 0.0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EnvConfig {
+    // Frames each action is held for. The rewards over them are added up.
+    pub frame_skip: u32,
+    // Episodes are cut off after this many steps, if set
+    pub max_steps: Option<u32>,
+    pub reward: RewardConfig,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            frame_skip: 4,
+            max_steps: None,
+            reward: RewardConfig::default(),
+        }
+    }
+}
+
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+}
+
+pub struct Env {
+    config: EnvConfig,
+    // The game, taken out of its `App`, which can't be sent between threads
+    world: World,
+    schedule: Schedule,
+    steps: u32,
+    points: i32,
+    lives: i32,
+}
+
+impl Env {
+    // Starts with a game from seed 0, so `step` works without a `reset` first
+    pub fn new(config: EnvConfig) -> Self {
+        let mut env = Env {
+            config,
+            world: World::default(),
+            schedule: Schedule::default(),
+            steps: 0,
+            points: 0,
+            lives: 0,
+        };
+
+        env.reset(0);
+        env
+    }
+
+    // Throws the current game away and starts a new one from the seed
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let mut app = headless_game(seed, Roster::default());
+        // The first frame only sets the game up
+        app.update();
+
+        self.world = std::mem::take(&mut app.world);
+        self.schedule = std::mem::take(&mut app.schedule);
+        self.steps = 0;
+
+        let score = &self.world.get_resource::<Scoreboard>().unwrap().players[0];
+        self.points = score.points;
+        self.lives = score.lives;
+
+        self.observation()
+    }
+
+    pub fn step(&mut self, action: ShipInput) -> Step {
+        let mut reward = 0.0;
+
+        for _ in 0..self.config.frame_skip.max(1) {
+            if self.is_over() {
+                break;
+            }
+
+            for (_, mut input) in self
+                .world
+                .query::<(&Player, &mut ShipInput)>()
+                .iter_mut(&mut self.world)
+                .filter(|(player, _)| player.id == 0)
+            {
+                *input = action;
+            }
+
+            self.schedule.run(&mut self.world);
+
+            let score = &self.world.get_resource::<Scoreboard>().unwrap().players[0];
+            let reward_config = &self.config.reward;
+            reward += (score.points - self.points) as f32 * reward_config.points
+                + (self.lives - score.lives) as f32 * reward_config.life_lost
+                + reward_config.survival / FIXED_FRAME_RATE as f32;
+
+            self.points = score.points;
+            self.lives = score.lives;
+        }
+
+        self.steps += 1;
+
+        let out_of_steps = matches!(self.config.max_steps, Some(max) if self.steps >= max);
+
+        Step {
+            observation: self.observation(),
+            reward,
+            done: self.is_over() || out_of_steps,
+        }
+    }
+
+    pub fn observation(&mut self) -> Observation {
+        observe_world(&mut self.world, 0)
+    }
+
+    // Whether the ship is out of lives
+    pub fn is_over(&self) -> bool {
+        *self
+            .world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+            != GameState::Game
+    }
+
+    // The game itself, e.g. for looking at more than the observation shows
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+}
+
+// Many environments stepped together, spread over a number of threads
+pub struct VecEnv {
+    envs: Vec<Env>,
+    threads: usize,
+}
+
+impl VecEnv {
+    pub fn new(count: usize, config: EnvConfig, threads: usize) -> Self {
+        VecEnv {
+            envs: (0..count).map(|_| Env::new(config.clone())).collect(),
+            threads: threads.max(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    // Resets every environment, each from its own seed
+    pub fn reset(&mut self, seeds: &[u64]) -> Vec<Observation> {
+        assert_eq!(
+            seeds.len(),
+            self.envs.len(),
+            "need one seed per environment"
+        );
+        self.run(|index, env| env.reset(seeds[index]))
+    }
+
+    // Resets one environment, e.g. once its episode is done
+    pub fn reset_one(&mut self, index: usize, seed: u64) -> Observation {
+        self.envs[index].reset(seed)
+    }
+
+    // Steps every environment with its own action. Environments whose
+    // episode is done stay that way until they're reset.
+    pub fn step(&mut self, actions: &[ShipInput]) -> Vec<Step> {
+        assert_eq!(
+            actions.len(),
+            self.envs.len(),
+            "need one action per environment"
+        );
+        self.run(|index, env| env.step(actions[index]))
+    }
+
+    pub fn envs_mut(&mut self) -> &mut [Env] {
+        &mut self.envs
+    }
+
+    // Runs `task` on every environment, splitting them into one batch per
+    // thread, and returns the results in environment order
+    fn run<T, F>(&mut self, task: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, &mut Env) -> T + Sync,
+    {
+        let batch_size = self.envs.len().div_ceil(self.threads).max(1);
+        let task = &task;
+
+        std::thread::scope(|scope| {
+            let batches: Vec<_> = self
+                .envs
+                .chunks_mut(batch_size)
+                .enumerate()
+                .map(|(batch, envs)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .enumerate()
+                            .map(|(index, env)| task(batch * batch_size + index, env))
+                            .collect::<Vec<T>>()
+                    })
+                })
+                .collect();
+
+            batches
+                .into_iter()
+                .flat_map(|batch| batch.join().expect("environment thread panicked"))
+                .collect()
+        })
+    }
+}
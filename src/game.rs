@@ -1,155 +1,678 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
 use std::f32::consts::PI;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
 
-use super::GameState;
+use crate::controls::ShipInput;
+use crate::pilot::drive_ships;
+use crate::power_ups::{
+    self, drop_power_ups, pick_up_power_ups, power_up_movement, power_up_sprite, tick_effects,
+    Effects, PowerUp, PowerUpKind, RAPID_FIRE_RATE, SCORE_MULTIPLIER,
+};
+use crate::weapons::{steer_missiles, WeaponKind, Weapons};
+use crate::GameState;
 use bevy::app::Events;
-use bevy::ecs::system::EntityCommands;
+use bevy::ecs::system::CommandQueue;
 use bevy::prelude::*;
-use bevy::sprite::collide_aabb::{collide, Collision};
-use rand::Rng;
+use bevy::sprite::collide_aabb::collide;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-use crate::despawn_screen;
+#[derive(Component, Clone)]
+pub struct Player {
+    pub id: usize,
+    pub velocity: Vec2,
+}
 
-#[derive(Component)]
-struct Player {
-    velocity: Vec2,
+// Textures and fonts used by the game. Without an asset server, e.g. when the
+// game runs headless, these are left as empty handles.
+pub struct GameAssets {
+    pub player: Handle<Image>,
+    pub asteroid: Handle<Image>,
+    pub font: Handle<Font>,
 }
 
-fn player_movement(
-    keys: Res<Input<KeyCode>>,
-    time: Res<Time>,
+impl FromWorld for GameAssets {
+    fn from_world(world: &mut World) -> Self {
+        match world.get_resource::<AssetServer>() {
+            Some(asset_server) => GameAssets {
+                player: asset_server.load("player.png"),
+                asteroid: asset_server.load("asteroid1.png"),
+                font: asset_server.load("Orbitron.ttf"),
+            },
+            None => GameAssets {
+                player: Handle::default(),
+                asteroid: Handle::default(),
+                font: Handle::default(),
+            },
+        }
+    }
+}
+
+// Ship colours, by player
+pub const PLAYER_COLORS: [Color; 4] = [Color::WHITE, Color::CYAN, Color::ORANGE, Color::LIME_GREEN];
+
+// How many people are playing and whether they're on the same side, picked on
+// the menu before the game starts
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Roster {
+    pub players: usize,
+    pub versus: bool,
+}
+
+impl Default for Roster {
+    fn default() -> Self {
+        Roster {
+            players: 1,
+            versus: false,
+        }
+    }
+}
+
+// When a versus match is over. Either limit can be turned off, but a match
+// with neither never ends.
+pub struct VersusRules {
+    pub frag_limit: Option<i32>,
+    pub time_limit: Option<f32>,
+}
+
+impl Default for VersusRules {
+    fn default() -> Self {
+        VersusRules {
+            frag_limit: Some(10),
+            time_limit: Some(180.0),
+        }
+    }
+}
+
+// Time left in a versus match
+pub struct MatchTimer(pub Timer);
+
+// How much game time the current frame covers. This follows the clock unless
+// `fixed` is set, which makes every frame the same length so a game can be
+// simulated again exactly, e.g. when rolling back.
+#[derive(Default)]
+pub struct FrameTime {
+    pub delta: Duration,
+    pub fixed: Option<Duration>,
+}
+
+impl FrameTime {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
+// The shortest and longest frames a game plays, which are also the only ones
+// a replay may have. The frame rate is capped so frames following the clock
+// are never shorter, and longer ones, e.g. while the window is dragged, are
+// played as the longest rather than letting everything jump.
+pub const MIN_FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 240);
+pub const MAX_FRAME_TIME: Duration = Duration::from_millis(100);
+
+fn update_frame_time(time: Option<Res<Time>>, mut frame_time: ResMut<FrameTime>) {
+    frame_time.delta = match frame_time.fixed {
+        Some(fixed) => fixed,
+        None => time.map_or(Duration::ZERO, |time| {
+            time.delta().clamp(MIN_FRAME_TIME, MAX_FRAME_TIME)
+        }),
+    };
+}
+
+fn limit_frame_rate(time: Option<Res<Time>>, frame_time: Res<FrameTime>) {
+    if frame_time.fixed.is_some() {
+        return;
+    }
+
+    if let Some(last_update) = time.and_then(|time| time.last_update()) {
+        let elapsed = last_update.elapsed();
+        if elapsed < MIN_FRAME_TIME {
+            std::thread::sleep(MIN_FRAME_TIME - elapsed);
+        }
+    }
+}
+
+// The frame rate of a game built by `headless_game`
+pub const FIXED_FRAME_RATE: f64 = 60.0;
+
+// A game with no window, input devices or clock behind it, which starts on its
+// first update. Every update plays one frame of fixed length, entirely on the
+// calling thread, so many games can run side by side and any of them plays out
+// the same way again given the same seed and inputs.
+pub fn headless_game(seed: u64, roster: Roster) -> App {
+    let mut app = App::empty();
+    app.add_stage(CoreStage::First, SystemStage::single_threaded())
+        .add_stage(CoreStage::PreUpdate, SystemStage::single_threaded())
+        .add_stage(CoreStage::Update, SystemStage::single_threaded())
+        .add_stage(CoreStage::PostUpdate, SystemStage::single_threaded())
+        .add_stage(CoreStage::Last, SystemStage::single_threaded())
+        .add_system_to_stage(CoreStage::Last, World::clear_trackers.exclusive_system())
+        .insert_resource(roster)
+        .insert_resource(GameSeed(seed))
+        .insert_resource(FrameTime {
+            delta: Duration::ZERO,
+            fixed: Some(Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE)),
+        })
+        .add_plugin(GamePlugin)
+        .add_state(GameState::Game);
+
+    app
+}
+
+// What the next game, or the one being played, is seeded with. Games started
+// from the same seed with the same inputs play out the same way.
+pub struct GameSeed(pub u64);
+
+// Random seeds are kept this small so they fit in a seed code
+pub const SEED_BITS: u32 = 40;
+
+impl GameSeed {
+    pub fn random() -> Self {
+        GameSeed(rand::random::<u64>() >> (64 - SEED_BITS))
+    }
+}
+
+impl Default for GameSeed {
+    fn default() -> Self {
+        GameSeed::random()
+    }
+}
+
+// Every random choice the game makes comes from here. It's seeded from
+// `GameSeed` when a game starts.
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(StdRng::from_entropy())
+    }
+}
+
+// How forgiving the game is, picked in the settings
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn lives(self) -> i32 {
+        match self {
+            Difficulty::Easy => 5,
+            Difficulty::Normal => 3,
+            Difficulty::Hard => 2,
+        }
+    }
+
+    // Time between new asteroids
+    pub fn asteroid_interval(self) -> f32 {
+        match self {
+            Difficulty::Easy => 3.0,
+            Difficulty::Normal => 2.0,
+            Difficulty::Hard => 1.25,
+        }
+    }
+}
+
+// How long a time attack game lasts
+const TIME_ATTACK_SECONDS: f32 = 120.0;
+
+// Classic waves start with this many large asteroids and add one each wave, up
+// to the most
+const CLASSIC_FIRST_WAVE: u32 = 4;
+const CLASSIC_MAX_WAVE: u32 = 11;
+
+// The pause between clearing a classic wave and the next one arriving
+const CLASSIC_WAVE_DELAY: f32 = 2.0;
+
+// Each survival asteroid comes this much sooner than the last, up to the limit
+const SURVIVAL_SPEEDUP: f32 = 0.97;
+const SURVIVAL_MIN_INTERVAL: f32 = 0.35;
+
+// Zen games stop sending asteroids in while this many are about
+const ZEN_MAX_ASTEROIDS: usize = 8;
+
+// The rules for a game that isn't versus, picked on the menu. Versus matches
+// have their own rules and ignore this.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, Serialize, Deserialize,
+)]
+pub enum GameMode {
+    // Waves of large asteroids, each bigger than the last, and a few lives
+    #[default]
+    Classic,
+    // As many points as possible before the clock runs out. Ships respawn for free.
+    TimeAttack,
+    // One life, and the asteroids keep coming faster
+    Survival,
+    // Nothing can hurt the ships, and the game goes on until it's left
+    Zen,
+}
+
+pub const GAME_MODES: [GameMode; 4] = [
+    GameMode::Classic,
+    GameMode::TimeAttack,
+    GameMode::Survival,
+    GameMode::Zen,
+];
+
+impl GameMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "CLASSIC",
+            GameMode::TimeAttack => "TIME ATTACK",
+            GameMode::Survival => "SURVIVAL",
+            GameMode::Zen => "ZEN",
+        }
+    }
+
+    // The name used in file names and URLs
+    pub fn key(self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::TimeAttack => "time-attack",
+            GameMode::Survival => "survival",
+            GameMode::Zen => "zen",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<GameMode> {
+        GAME_MODES.iter().copied().find(|mode| mode.key() == key)
+    }
+
+    // Whether ships can run out of lives. If not they respawn for free.
+    pub fn has_lives(self) -> bool {
+        matches!(self, GameMode::Classic | GameMode::Survival)
+    }
+
+    pub fn lives(self, difficulty: Difficulty) -> i32 {
+        match self {
+            GameMode::Classic => difficulty.lives(),
+            _ => 0,
+        }
+    }
+
+    pub fn time_limit(self) -> Option<f32> {
+        match self {
+            GameMode::TimeAttack => Some(TIME_ATTACK_SECONDS),
+            _ => None,
+        }
+    }
+
+    // Time between new asteroids, or in classic games between waves
+    fn asteroid_interval(self, difficulty: Difficulty) -> f32 {
+        match self {
+            GameMode::Classic => CLASSIC_WAVE_DELAY,
+            GameMode::Zen => difficulty.asteroid_interval() * 1.5,
+            GameMode::TimeAttack | GameMode::Survival => difficulty.asteroid_interval(),
+        }
+    }
+}
+
+// Where a player's ship appears at the start of the game and after dying.
+// Co-op ships start side by side, versus ships start spread around the arena.
+pub fn spawn_point(id: usize, roster: &Roster) -> Vec3 {
+    if roster.versus {
+        let angle = PI / 4.0 + id as f32 * 2.0 * PI / roster.players as f32;
+        Vec3::new(angle.cos() * 400.0, angle.sin() * 250.0, 0.0)
+    } else {
+        let spacing = 100.0;
+        let x = (id as f32 - (roster.players as f32 - 1.0) / 2.0) * spacing;
+        Vec3::new(x, 0.0, 0.0)
+    }
+}
+
+// The stages of a game frame, in the order they run
+#[derive(SystemLabel, Clone, Copy, Hash, Debug, PartialEq, Eq)]
+pub enum GameSystem {
+    // Filling in each ship's `ShipInput`, from its `Pilot` or the network
+    Input,
+    // Ships acting on their input
+    Fire,
+    Spawn,
+    Movement,
+    Collision,
+    Damage,
+    Hud,
+}
+
+fn player_fire(
+    time: Res<FrameTime>,
     mut bullet_fire_event: EventWriter<BulletFireEvent>,
-    mut bullet_fire_timer: ResMut<BulletFireTimer>,
-    mut query: Query<(&mut Player, &mut Transform)>,
+    mut query: Query<(Entity, &ShipInput, &Effects, &mut Weapons)>,
 ) {
-    let (mut player, mut transform) = query.single_mut();
-    let dt = time.delta_seconds_f64() as f32;
-    let rotation = &mut transform.rotation;
+    for (entity, input, effects, mut weapons) in query.iter_mut() {
+        let rate = if effects.has(PowerUpKind::RapidFire) {
+            RAPID_FIRE_RATE
+        } else {
+            1.0
+        };
 
-    let angle = quat_to_angle(rotation);
+        if weapons.trigger(input, time.delta, rate) {
+            bullet_fire_event.send(BulletFireEvent(entity));
+        }
+    }
+}
+
+fn player_movement(
+    time: Res<FrameTime>,
+    mut stats: ResMut<RunStats>,
+    mut query: Query<(&mut Player, &ShipInput, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (mut player, input, mut transform) in query.iter_mut() {
+        step_ship(&mut player.velocity, &mut transform, input, dt);
+
+        if let Some(stats) = stats.players.get_mut(player.id) {
+            stats.time_survived += time.delta;
+        }
+    }
+}
+
+// Lets a ship vanish and reappear somewhere random, then has to recharge
+#[derive(Component, Clone)]
+pub struct Hyperspace {
+    cooldown: Timer,
+    // Whether hyperspace was held last frame, so holding it only jumps once
+    button_held: bool,
+}
+
+impl Hyperspace {
+    fn new() -> Self {
+        let mut cooldown = Timer::from_seconds(3.0, false);
+        // Ships start out able to jump
+        cooldown.set_elapsed(cooldown.duration());
+
+        Hyperspace {
+            cooldown,
+            button_held: false,
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        self.cooldown.finished()
+    }
+}
+
+fn player_hyperspace(
+    time: Res<FrameTime>,
+    mut rng: ResMut<GameRng>,
+    mut query: Query<(&mut Player, &ShipInput, &mut Hyperspace, &mut Transform)>,
+) {
+    for (mut player, input, mut hyperspace, mut transform) in query.iter_mut() {
+        hyperspace.cooldown.tick(time.delta);
 
-    if keys.just_pressed(KeyCode::J) {
-        bullet_fire_event.send(BulletFireEvent);
-        bullet_fire_timer.0.reset()
-    } else if keys.pressed(KeyCode::J) {
-        if bullet_fire_timer.0.tick(time.delta()).just_finished() {
-            bullet_fire_event.send(BulletFireEvent);
+        if input.hyperspace && !hyperspace.button_held && hyperspace.ready() {
+            transform.translation.x = rng.0.gen_range(-640.0..640.0);
+            transform.translation.y = rng.0.gen_range(-360.0..360.0);
+            player.velocity = Vec2::default();
+            hyperspace.cooldown.reset();
         }
+
+        hyperspace.button_held = input.hyperspace;
     }
+}
+
+// Moves a ship forward by one frame. Network clients use this to predict
+// their own ship ahead of the server.
+pub fn step_ship(velocity: &mut Vec2, transform: &mut Transform, input: &ShipInput, dt: f32) {
+    let rotation = &mut transform.rotation;
+
+    let angle = quat_to_angle(rotation);
 
-    if keys.pressed(KeyCode::W) {
-        player.velocity.x += angle.cos() * 500.0 * dt;
-        player.velocity.y += angle.sin() * 500.0 * dt;
+    if input.thrust {
+        velocity.x += angle.cos() * 500.0 * dt;
+        velocity.y += angle.sin() * 500.0 * dt;
     }
-    if keys.pressed(KeyCode::S) {
-        player.velocity.x += angle.cos() * -500.0 * dt;
-        player.velocity.y += angle.sin() * -500.0 * dt;
+    if input.reverse {
+        velocity.x += angle.cos() * -500.0 * dt;
+        velocity.y += angle.sin() * -500.0 * dt;
     }
 
-    if keys.pressed(KeyCode::A) {
+    if input.left {
         *rotation = rotation.mul_quat(Quat::from_rotation_z(2.0 * dt));
     }
-    if keys.pressed(KeyCode::D) {
+    if input.right {
         *rotation = rotation.mul_quat(Quat::from_rotation_z(-2.0 * dt));
     }
 
-    let magnitude = (player.velocity.x.powf(2.0) + player.velocity.y.powf(2.0)).sqrt();
+    let magnitude = (velocity.x.powf(2.0) + velocity.y.powf(2.0)).sqrt();
     // If the total velocity is greater than 500, we normalize the vector
     if magnitude > 500.0 {
-        player.velocity.x *= 500.0 / magnitude;
-        player.velocity.y *= 500.0 / magnitude;
+        velocity.x *= 500.0 / magnitude;
+        velocity.y *= 500.0 / magnitude;
     }
     // Otherwise, we simply apply force for friction
     else if magnitude >= 0.2 {
-        player.velocity.x *= (magnitude - 1.0) / magnitude;
-        player.velocity.y *= (magnitude - 1.0) / magnitude;
+        velocity.x *= (magnitude - 1.0) / magnitude;
+        velocity.y *= (magnitude - 1.0) / magnitude;
     }
     // Set velocity to zero
     else {
-        player.velocity.x = 0.0;
-        player.velocity.y = 0.0;
+        velocity.x = 0.0;
+        velocity.y = 0.0;
     }
 
     let translation = &mut transform.translation;
 
-    translation.x = translation.x + player.velocity.x * dt;
-    translation.y = translation.y + player.velocity.y * dt;
+    translation.x += velocity.x * dt;
+    translation.y += velocity.y * dt;
 
     wrap_position(translation);
 }
 
-struct PlayerDeathEvent;
+struct PlayerDeathEvent {
+    ship: Entity,
+    // The player whose bullet it was, if the ship was shot down
+    killer: Option<usize>,
+}
 
+#[allow(clippy::too_many_arguments)]
 fn player_death(
+    mut commands: Commands,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
     mut scoreboard: ResMut<Scoreboard>,
+    mut stats: ResMut<RunStats>,
     mut death_event: EventReader<PlayerDeathEvent>,
     mut game_state: ResMut<State<GameState>>,
-    mut query: Query<(&mut Player, &mut Transform)>,
+    mut query: Query<(&mut Player, &mut Transform, &mut Effects, &mut Weapons)>,
 ) {
-    let (mut player, mut transform) = query.single_mut();
+    let mut died = false;
+    // A ship that was hit by more than one thing this frame only dies once
+    let mut dead = HashSet::new();
+
+    for event in death_event.iter() {
+        if !dead.insert(event.ship) {
+            continue;
+        }
 
-    for _ in death_event.iter() {
-        scoreboard.lives -= 1;
+        // The ship may already be gone
+        let (mut player, mut transform, mut effects, mut weapons) = match query.get_mut(event.ship)
+        {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        // Out of lives ships are already on their way out
+        if scoreboard.players[player.id].lives < 0 {
+            continue;
+        }
 
-        if scoreboard.lives < 0 {
-            game_state.set(GameState::GameOver).unwrap();
+        if let Some(killer) = event.killer {
+            scoreboard.players[killer].frags += 1;
+        }
+
+        // Dying ends a combo, and any power-ups, and loses the weapons picked up
+        if let Some(stats) = stats.players.get_mut(player.id) {
+            stats.combo = 0;
+        }
+        effects.0.clear();
+        *weapons = Weapons::default();
+
+        // Versus ships respawn for as long as the match lasts, and so do ships
+        // in modes without lives
+        if !roster.versus && mode.has_lives() {
+            scoreboard.players[player.id].lives -= 1;
+            died = true;
+        }
+
+        if scoreboard.players[player.id].lives < 0 {
+            commands.entity(event.ship).despawn_recursive();
         } else {
             player.velocity = Vec2::default();
             transform.rotation = Quat::from_rotation_z(PI / 2.0);
-            transform.translation.x = 0.0;
-            transform.translation.y = 0.0;
+            transform.translation = spawn_point(player.id, &roster);
         }
     }
+
+    // The game only ends once every player is out
+    if died && scoreboard.players.iter().all(|score| score.lives < 0) {
+        game_state.set(GameState::GameOver).unwrap();
+    }
 }
 
-#[derive(Component)]
-struct Asteroid {
-    speed: f32,
-    size: i32,
+// The time limit for the game being set up, if it has one
+pub(crate) fn time_limit(roster: &Roster, rules: &VersusRules, mode: GameMode) -> Option<f32> {
+    if roster.versus {
+        rules.time_limit
+    } else {
+        mode.time_limit()
+    }
+}
+
+// Ends a versus match once someone reaches the frag limit, and any timed game
+// once time runs out
+fn match_end(
+    time: Res<FrameTime>,
+    roster: Res<Roster>,
+    rules: Res<VersusRules>,
+    mode: Res<GameMode>,
+    scoreboard: Res<Scoreboard>,
+    mut match_timer: ResMut<MatchTimer>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let frag_limit_reached = roster.versus
+        && matches!(
+            rules.frag_limit,
+            Some(limit) if scoreboard.players.iter().any(|score| score.frags >= limit)
+        );
+    let time_up =
+        time_limit(&roster, &rules, *mode).is_some() && match_timer.0.tick(time.delta).finished();
+
+    if frag_limit_reached || time_up {
+        game_state.set(GameState::GameOver).unwrap();
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Asteroid {
+    pub speed: f32,
+    pub size: i32,
 }
 
 struct AsteroidTimer(Timer);
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_asteroid(
     mut commands: Commands,
-    time: Res<Time>,
-    asset_server: Res<AssetServer>,
+    time: Res<FrameTime>,
+    assets: Res<GameAssets>,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    mut rng: ResMut<GameRng>,
     mut timer: ResMut<AsteroidTimer>,
+    mut stats: ResMut<RunStats>,
+    asteroid_query: Query<(), With<Asteroid>>,
 ) {
-    let asteroid_texture = asset_server.load("asteroid1.png");
+    let rng = &mut rng.0;
+    let mode = if roster.versus { None } else { Some(*mode) };
 
-    if timer.0.tick(time.delta()).just_finished() {
-        let mut rng = rand::thread_rng();
+    // Classic waves come in all at once, a little while after the last is cleared
+    if mode == Some(GameMode::Classic) {
+        if asteroid_query.iter().next().is_some() {
+            timer.0.reset();
+        } else if timer.0.tick(time.delta).just_finished() {
+            stats.wave += 1;
 
-        let translation = if rng.gen_bool(0.5) {
-            Vec3::new(1280.0, rng.gen_range(0.0..720.0), 0.0)
-        } else {
-            Vec3::new(rng.gen_range(0.0..1280.0), 720.0, 0.0)
-        };
+            for _ in 0..(CLASSIC_FIRST_WAVE + stats.wave - 1).min(CLASSIC_MAX_WAVE) {
+                stats.asteroids_released += 1;
+                add_edge_asteroid(&mut commands, &assets, rng);
+            }
+        }
+        return;
+    }
 
-        commands
-            .spawn_bundle(SpriteBundle {
-                transform: Transform {
-                    translation,
-                    rotation: Quat::from_rotation_z(rand::thread_rng().gen_range(-180.0..180.0)),
-                    scale: Vec3::new(3.0, 3.0, 0.0),
-                },
-                sprite: Sprite {
-                    color: Color::WHITE,
-                    ..Default::default()
-                },
-                texture: asteroid_texture,
-                ..Default::default()
-            })
-            .insert(Asteroid {
-                speed: 100.0,
-                size: 3,
-            })
-            .insert(Collider::Asteroid);
+    if !timer.0.tick(time.delta).just_finished() {
+        return;
+    }
+
+    if mode == Some(GameMode::Zen) && asteroid_query.iter().count() >= ZEN_MAX_ASTEROIDS {
+        return;
+    }
+
+    stats.asteroids_released += 1;
+    stats.wave = (stats.asteroids_released - 1) / WAVE_SIZE + 1;
+    add_edge_asteroid(&mut commands, &assets, rng);
+
+    if mode == Some(GameMode::Survival) {
+        let interval = timer.0.duration().as_secs_f32() * SURVIVAL_SPEEDUP;
+        timer
+            .0
+            .set_duration(Duration::from_secs_f32(interval.max(SURVIVAL_MIN_INTERVAL)));
     }
 }
 
-fn asteroid_movement(time: Res<Time>, mut query: Query<(&mut Asteroid, &mut Transform)>) {
-    let dt = time.delta_seconds_f64() as f32;
+// A large asteroid drifting in from the edge of the arena
+fn add_edge_asteroid(commands: &mut Commands, assets: &GameAssets, rng: &mut StdRng) {
+    let translation = if rng.gen_bool(0.5) {
+        Vec3::new(1280.0, rng.gen_range(0.0..720.0), 0.0)
+    } else {
+        Vec3::new(rng.gen_range(0.0..1280.0), 720.0, 0.0)
+    };
+
+    add_asteroid(
+        commands,
+        &assets.asteroid,
+        Asteroid {
+            speed: 100.0,
+            size: 3,
+        },
+        Transform {
+            translation,
+            rotation: Quat::from_rotation_z(rng.gen_range(-180.0..180.0)),
+            scale: Vec3::new(3.0, 3.0, 0.0),
+        },
+    );
+}
+
+fn add_asteroid(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    asteroid: Asteroid,
+    transform: Transform,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            transform,
+            sprite: Sprite {
+                color: Color::WHITE,
+                ..Default::default()
+            },
+            texture: texture.clone(),
+            ..Default::default()
+        })
+        .insert(asteroid)
+        .insert(Collider::Asteroid);
+}
+
+fn asteroid_movement(time: Res<FrameTime>, mut query: Query<(&mut Asteroid, &mut Transform)>) {
+    let dt = time.delta_seconds();
 
     for (asteroid, mut transform) in query.iter_mut() {
         let angle = quat_to_angle(&transform.rotation);
@@ -162,18 +685,31 @@ fn asteroid_movement(time: Res<Time>, mut query: Query<(&mut Asteroid, &mut Tran
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn asteroid_collision(
     mut commands: Commands,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
     mut player_death_event: EventWriter<PlayerDeathEvent>,
+    mut target_destroyed_event: EventWriter<TargetDestroyed>,
+    mut stats: ResMut<RunStats>,
     mut asteroid_query: Query<(Entity, &mut Asteroid, &Transform)>,
-    mut score: ResMut<Scoreboard>,
-    asset_server: Res<AssetServer>,
-    collider_query: Query<(Entity, &Collider, &Transform)>,
+    assets: Res<GameAssets>,
+    mut collider_query: Query<(Entity, &Collider, &Transform, Option<&mut Bullet>)>,
+    effects_query: Query<&Effects>,
+    mut destroyed: ResMut<DestroyedAsteroids>,
 ) {
-    let asteroid_texture = asset_server.load("asteroid1.png");
+    destroyed.0.clear();
+
+    // Ships before bullets, so a ship flying into an asteroid as it's shot
+    // still dies. Queries meet each kind in the order the world first stored
+    // one, which a game rolled back or restored from a snapshot needn't share.
+    let mut colliders: Vec<_> = collider_query.iter_mut().collect();
+    colliders.sort_by_key(|(_, collider, _, _)| !matches!(collider, Collider::Player));
 
     for (asteroid_entity, asteroid, asteroid_transform) in asteroid_query.iter_mut() {
-        for (collider_entity, collider, transform) in collider_query.iter() {
+        for (collider_entity, collider, transform, bullet) in colliders.iter_mut() {
+            let collider_entity = *collider_entity;
             let collision = collide(
                 asteroid_transform.translation,
                 asteroid_transform.scale.truncate() * 32.0,
@@ -181,70 +717,57 @@ fn asteroid_collision(
                 transform.scale.truncate(),
             );
 
-            if let Some(_) = collision {
-                match *collider {
+            if collision.is_some() {
+                match **collider {
+                    // Rock-on-rock collisions are handled by `asteroid_bounce`
                     Collider::Asteroid => {}
+                    // It hit another asteroid earlier this frame and is only waiting to despawn
+                    Collider::Bullet if bullet.as_deref().is_some_and(is_spent) => {}
                     Collider::Bullet => {
-                        commands.entity(collider_entity).despawn_recursive();
+                        if !bullet
+                            .as_ref()
+                            .is_some_and(|bullet| bullet.weapon.pierces())
+                        {
+                            commands.entity(collider_entity).despawn_recursive();
+                        }
                         commands.entity(asteroid_entity).despawn_recursive();
 
-                        if asteroid.size > 1 {
-                            let angle = quat_to_angle(&asteroid_transform.rotation);
-                            let new_size = asteroid.size - 1;
-
-                            score.points += 100;
-
-                            commands
-                                .spawn_bundle(SpriteBundle {
-                                    transform: Transform {
-                                        translation: asteroid_transform.translation,
-                                        rotation: Quat::from_rotation_z(angle + (PI / 4.0)),
-                                        scale: Vec3::new(
-                                            new_size as f32,
-                                            new_size as f32,
-                                            new_size as f32,
-                                        ),
-                                    },
-                                    sprite: Sprite {
-                                        color: Color::WHITE,
-                                        ..Default::default()
-                                    },
-                                    texture: asteroid_texture.clone(),
-                                    ..Default::default()
-                                })
-                                .insert(Asteroid {
-                                    speed: asteroid.speed * 2.0,
-                                    size: new_size,
-                                })
-                                .insert(Collider::Asteroid);
-
-                            commands
-                                .spawn_bundle(SpriteBundle {
-                                    transform: Transform {
-                                        translation: asteroid_transform.translation,
-                                        rotation: Quat::from_rotation_z(angle - (PI / 4.0)),
-                                        scale: Vec3::new(
-                                            new_size as f32,
-                                            new_size as f32,
-                                            new_size as f32,
-                                        ),
-                                    },
-                                    sprite: Sprite {
-                                        color: Color::WHITE,
-                                        ..Default::default()
-                                    },
-                                    texture: asteroid_texture.clone(),
-                                    ..Default::default()
-                                })
-                                .insert(Asteroid {
-                                    speed: asteroid.speed * 2.0,
-                                    size: new_size,
-                                })
-                                .insert(Collider::Asteroid);
+                        if let Some(bullet) = bullet {
+                            if let Some(stats) = stats.players.get_mut(bullet.owner) {
+                                stats.record_kill(!bullet.scored);
+                                stats.asteroids_destroyed
+                                    [asteroid.size.clamp(1, 3) as usize - 1] += 1;
+                            }
+
+                            target_destroyed_event.send(TargetDestroyed {
+                                target: Target::Asteroid {
+                                    size: asteroid.size,
+                                },
+                                player: bullet.owner,
+                                translation: asteroid_transform.translation,
+                            });
+                            bullet.scored = true;
                         }
+
+                        split_asteroid(
+                            &mut commands,
+                            &assets.asteroid,
+                            &asteroid,
+                            asteroid_transform,
+                        );
+                        destroyed.0.push(asteroid_entity);
+                        // It's gone, so nothing else can hit it this frame
+                        break;
                     }
+                    // Asteroids pass harmlessly through zen ships
+                    Collider::Player if !roster.versus && *mode == GameMode::Zen => {}
+                    Collider::Player
+                        if effects_query.get(collider_entity).is_ok_and(is_shielded) => {}
                     Collider::Player => {
-                        player_death_event.send(PlayerDeathEvent);
+                        player_death_event.send(PlayerDeathEvent {
+                            ship: collider_entity,
+                            killer: None,
+                        });
                     }
                 }
             }
@@ -252,18 +775,238 @@ fn asteroid_collision(
     }
 }
 
-#[derive(Component)]
-struct Bullet {
-    speed: f32,
+// Breaks an asteroid into two smaller, faster pieces. The smallest asteroids
+// leave nothing behind.
+fn split_asteroid(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    asteroid: &Asteroid,
+    transform: &Transform,
+) {
+    if asteroid.size <= 1 {
+        return;
+    }
+
+    let angle = quat_to_angle(&transform.rotation);
+    let new_size = asteroid.size - 1;
+
+    for offset in [PI / 4.0, -PI / 4.0] {
+        add_asteroid(
+            commands,
+            texture,
+            Asteroid {
+                speed: asteroid.speed * 2.0,
+                size: new_size,
+            },
+            Transform {
+                translation: transform.translation,
+                rotation: Quat::from_rotation_z(angle + offset),
+                scale: Vec3::new(new_size as f32, new_size as f32, new_size as f32),
+            },
+        );
+    }
+}
+
+// Asteroids destroyed this frame. Despawning waits until the end of the stage,
+// so until then they're still in every query and must be skipped by hand.
+#[derive(Default)]
+pub struct DestroyedAsteroids(pub Vec<Entity>);
+
+// Optional rules for asteroids hitting each other. When disabled, asteroids
+// pass through one another like in the arcade original.
+pub struct AsteroidPhysics {
+    pub enabled: bool,
+    // Impact energy above which two colliding asteroids crack each other
+    pub crack_energy: Option<f32>,
+}
+
+impl Default for AsteroidPhysics {
+    fn default() -> Self {
+        AsteroidPhysics {
+            enabled: false,
+            crack_energy: Some(40000.0),
+        }
+    }
+}
+
+// Bounces asteroids off each other with an elastic collision, treating each
+// asteroid as a circle whose mass is proportional to its size.
+fn asteroid_bounce(
+    mut commands: Commands,
+    physics: Res<AsteroidPhysics>,
+    assets: Res<GameAssets>,
+    mut query: Query<(Entity, &mut Asteroid, &mut Transform)>,
+    mut destroyed: ResMut<DestroyedAsteroids>,
+) {
+    if !physics.enabled {
+        return;
+    }
+
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([(entity_a, mut a, mut transform_a), (entity_b, mut b, mut transform_b)]) =
+        combinations.fetch_next()
+    {
+        if destroyed.0.contains(&entity_a) || destroyed.0.contains(&entity_b) {
+            continue;
+        }
+
+        let offset = (transform_b.translation - transform_a.translation).truncate();
+        let distance = offset.length();
+        let min_distance = (a.size + b.size) as f32 * 16.0;
+
+        if distance >= min_distance || distance == 0.0 {
+            continue;
+        }
+
+        let normal = offset / distance;
+        let velocity_a = asteroid_velocity(&a, &transform_a);
+        let velocity_b = asteroid_velocity(&b, &transform_b);
+
+        // Push the asteroids apart so they don't stay stuck inside each other
+        let push = (normal * (min_distance - distance) / 2.0).extend(0.0);
+        transform_a.translation -= push;
+        transform_b.translation += push;
+
+        let closing_speed = (velocity_a - velocity_b).dot(normal);
+        // Already moving apart
+        if closing_speed <= 0.0 {
+            continue;
+        }
+
+        let mass_a = a.size as f32;
+        let mass_b = b.size as f32;
+        let impulse = 2.0 * closing_speed / (mass_a + mass_b);
+
+        set_asteroid_velocity(
+            &mut a,
+            &mut transform_a,
+            velocity_a - normal * impulse * mass_b,
+        );
+        set_asteroid_velocity(
+            &mut b,
+            &mut transform_b,
+            velocity_b + normal * impulse * mass_a,
+        );
+
+        let reduced_mass = mass_a * mass_b / (mass_a + mass_b);
+        let energy = 0.5 * reduced_mass * closing_speed.powf(2.0);
+
+        if matches!(physics.crack_energy, Some(threshold) if energy > threshold) {
+            for (entity, asteroid, transform) in
+                [(entity_a, &a, &transform_a), (entity_b, &b, &transform_b)]
+            {
+                commands.entity(entity).despawn_recursive();
+                split_asteroid(&mut commands, &assets.asteroid, asteroid, transform);
+                destroyed.0.push(entity);
+            }
+        }
+    }
+}
+
+pub fn asteroid_velocity(asteroid: &Asteroid, transform: &Transform) -> Vec2 {
+    let angle = quat_to_angle(&transform.rotation);
+    Vec2::new(angle.cos(), angle.sin()) * asteroid.speed
+}
+
+// Asteroids travel in the direction they're facing, so changing velocity
+// means turning the asteroid as well as changing its speed.
+fn set_asteroid_velocity(asteroid: &mut Asteroid, transform: &mut Transform, velocity: Vec2) {
+    asteroid.speed = velocity.length();
+    transform.rotation = Quat::from_rotation_z(velocity.y.atan2(velocity.x));
 }
 
-struct BulletFireTimer(Timer);
-struct BulletFireEvent;
+pub const BULLET_SPEED: f32 = 1000.0;
 
-fn bullet_movement(time: Res<Time>, mut query: Query<(&mut Bullet, &mut Transform)>) {
-    let dt = time.delta_seconds_f64() as f32;
+// Anything fired from a ship, whatever the weapon
+#[derive(Component, Clone)]
+pub struct Bullet {
+    pub speed: f32,
+    // The player who fired it
+    pub owner: usize,
+    pub weapon: WeaponKind,
+    // Time left before it burns out, for weapons with a short range
+    pub range: Option<Timer>,
+    // Whether it has hit anything yet. However many things a projectile goes
+    // through, it only counts as one hit.
+    pub scored: bool,
+}
+
+// Friendly fire: in versus, bullets take down any ship except the one that fired them
+fn bullet_collision(
+    mut commands: Commands,
+    roster: Res<Roster>,
+    mut stats: ResMut<RunStats>,
+    mut player_death_event: EventWriter<PlayerDeathEvent>,
+    mut bullet_query: Query<(Entity, &mut Bullet, &Transform)>,
+    player_query: Query<(Entity, &Player, &Transform, &Effects)>,
+) {
+    if !roster.versus {
+        return;
+    }
+
+    // Ships already shot down this frame
+    let mut downed = HashSet::new();
+
+    for (bullet_entity, mut bullet, bullet_transform) in bullet_query.iter_mut() {
+        if is_spent(&bullet) {
+            continue;
+        }
+
+        for (player_entity, player, player_transform, effects) in player_query.iter() {
+            if player.id == bullet.owner {
+                continue;
+            }
+
+            let collision = collide(
+                bullet_transform.translation,
+                bullet_transform.scale.truncate(),
+                player_transform.translation,
+                Vec2::new(32.0, 32.0),
+            );
+
+            if collision.is_some() {
+                if !bullet.weapon.pierces() {
+                    commands.entity(bullet_entity).despawn_recursive();
+                }
+
+                // Shields soak bullets up, and a ship can only go down once
+                if is_shielded(effects) || !downed.insert(player_entity) {
+                    break;
+                }
+
+                if let Some(stats) = stats.players.get_mut(bullet.owner) {
+                    stats.record_kill(!bullet.scored);
+                }
+                bullet.scored = true;
+
+                player_death_event.send(PlayerDeathEvent {
+                    ship: player_entity,
+                    killer: Some(bullet.owner),
+                });
+                break;
+            }
+        }
+    }
+}
+
+// Sent with the ship that is firing
+struct BulletFireEvent(Entity);
+
+fn bullet_movement(
+    mut commands: Commands,
+    time: Res<FrameTime>,
+    mut query: Query<(Entity, &mut Bullet, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut bullet, mut transform) in query.iter_mut() {
+        if let Some(range) = &mut bullet.range {
+            if range.tick(time.delta).finished() {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        }
 
-    for (bullet, mut transform) in query.iter_mut() {
         let angle = quat_to_angle(&transform.rotation);
         let translation = &mut transform.translation;
 
@@ -276,42 +1019,356 @@ fn bullet_movement(time: Res<Time>, mut query: Query<(&mut Bullet, &mut Transfor
 
 fn bullet_fire(
     mut commands: Commands,
+    mut stats: ResMut<RunStats>,
     mut bullet_fire_event: EventReader<BulletFireEvent>,
-    player_query: Query<(&mut Player, &mut Transform)>,
+    player_query: Query<(&Player, &Transform, &Weapons)>,
 ) {
-    let (_, transform) = player_query.single();
+    for BulletFireEvent(entity) in bullet_fire_event.iter() {
+        if let Ok((player, transform, weapons)) = player_query.get(*entity) {
+            let angle = quat_to_angle(&transform.rotation);
 
-    for _ in bullet_fire_event.iter() {
-        let angle = quat_to_angle(&transform.rotation);
+            for (bullet, transform) in weapons
+                .current
+                .fire(player.id, transform.translation, angle)
+            {
+                if let Some(stats) = stats.players.get_mut(player.id) {
+                    stats.shots_fired += 1;
+                }
 
-        commands
-            .spawn_bundle(SpriteBundle {
-                transform: Transform {
-                    translation: transform.translation,
-                    rotation: Quat::from_rotation_z(angle),
-                    scale: Vec3::new(12.0, 12.0, 0.0),
-                },
-                sprite: Sprite {
-                    color: Color::WHITE,
-                    ..Default::default()
-                },
+                add_bullet(&mut commands, bullet, transform);
+            }
+        }
+    }
+}
+
+fn is_shielded(effects: &Effects) -> bool {
+    effects.has(PowerUpKind::Shield)
+}
+
+// A shot that's hit something and doesn't go on through
+fn is_spent(bullet: &Bullet) -> bool {
+    bullet.scored && !bullet.weapon.pierces()
+}
+
+fn add_bullet(commands: &mut Commands, bullet: Bullet, transform: Transform) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            transform,
+            sprite: Sprite {
+                color: PLAYER_COLORS[bullet.owner % PLAYER_COLORS.len()],
                 ..Default::default()
-            })
-            .insert(Bullet { speed: 1000.0 })
-            .insert(Collider::Bullet);
+            },
+            ..Default::default()
+        })
+        .insert(bullet)
+        .insert(Collider::Bullet);
+}
+
+// Large asteroids per wave, in modes where asteroids drift in steadily rather
+// than in waves. There waves are only counted, for the run summary.
+const WAVE_SIZE: u32 = 10;
+
+// Kills closer together than this make a combo
+const COMBO_SECONDS: f32 = 2.0;
+
+// How the run went, for the summary on the game over screen
+#[derive(Clone, Default)]
+pub struct RunStats {
+    pub players: Vec<PlayerStats>,
+    // Large asteroids released so far
+    pub asteroids_released: u32,
+    // Zero until the first asteroid arrives
+    pub wave: u32,
+}
+
+#[derive(Clone, Default)]
+pub struct PlayerStats {
+    // Asteroids shot down, by size from small to large
+    pub asteroids_destroyed: [u32; 3],
+    pub shots_fired: u32,
+    // Shots that hit an asteroid or a ship
+    pub hits: u32,
+    // How long the ship was in play
+    pub time_survived: Duration,
+    pub best_combo: u32,
+    combo: u32,
+    // `time_survived` at the last kill
+    last_kill: Option<Duration>,
+}
+
+impl PlayerStats {
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            0.0
+        } else {
+            self.hits as f32 / self.shots_fired as f32
+        }
+    }
+
+    // A kill by one of the player's shots, which only counts as a hit if it's
+    // the first thing the shot has hit
+    fn record_kill(&mut self, first_hit: bool) {
+        if first_hit {
+            self.hits += 1;
+        }
+
+        let now = self.time_survived;
+        self.combo = match self.last_kill {
+            Some(last) if (now - last).as_secs_f32() <= COMBO_SECONDS => self.combo + 1,
+            _ => 1,
+        };
+        self.best_combo = self.best_combo.max(self.combo);
+        self.last_kill = Some(now);
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerScore {
+    pub lives: i32,
+    pub points: i32,
+    // Other ships shot down, in versus
+    pub frags: i32,
+}
+
+// Lives and points for each player, indexed by player id
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub players: Vec<PlayerScore>,
+}
+
+// Something the player shot down
+pub enum Target {
+    Asteroid {
+        size: i32,
+    },
+    // Saucers aren't in the game yet, but the score table already has entries for them
+    #[allow(dead_code)]
+    Saucer {
+        small: bool,
+    },
+}
+
+pub struct TargetDestroyed {
+    pub target: Target,
+    // The player who gets the points
+    pub player: usize,
+    // Where the target was
+    pub translation: Vec3,
+}
+
+// Points awarded for each kind of target
+#[derive(Clone)]
+pub struct ScoreTable {
+    pub large_asteroid: i32,
+    pub medium_asteroid: i32,
+    pub small_asteroid: i32,
+    pub large_saucer: i32,
+    pub small_saucer: i32,
+}
+
+impl Default for ScoreTable {
+    fn default() -> Self {
+        ScoreTable {
+            large_asteroid: 20,
+            medium_asteroid: 50,
+            small_asteroid: 100,
+            large_saucer: 200,
+            small_saucer: 1000,
+        }
+    }
+}
+
+// The score table for each game mode. Anything can insert its own tables
+// before a game starts, and a mode without one scores by the default table.
+pub struct ScoreTables(pub BTreeMap<GameMode, ScoreTable>);
+
+impl Default for ScoreTables {
+    fn default() -> Self {
+        ScoreTables(
+            GAME_MODES
+                .iter()
+                .map(|mode| (*mode, ScoreTable::default()))
+                .collect(),
+        )
+    }
+}
+
+impl ScoreTables {
+    pub fn get(&self, mode: GameMode) -> ScoreTable {
+        self.0.get(&mode).cloned().unwrap_or_default()
+    }
+}
+
+impl ScoreTable {
+    pub fn points(&self, target: &Target) -> i32 {
+        match *target {
+            Target::Asteroid { size } if size >= 3 => self.large_asteroid,
+            Target::Asteroid { size: 2 } => self.medium_asteroid,
+            Target::Asteroid { .. } => self.small_asteroid,
+            Target::Saucer { small: false } => self.large_saucer,
+            Target::Saucer { small: true } => self.small_saucer,
+        }
+    }
+}
+
+fn award_points(
+    score_tables: Res<ScoreTables>,
+    mode: Res<GameMode>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut target_destroyed_event: EventReader<TargetDestroyed>,
+    ship_query: Query<(&Player, &Effects)>,
+) {
+    let score_table = score_tables.get(*mode);
+
+    for event in target_destroyed_event.iter() {
+        let multiplied = ship_query.iter().any(|(player, effects)| {
+            player.id == event.player && effects.has(PowerUpKind::ScoreMultiplier)
+        });
+        let multiplier = if multiplied { SCORE_MULTIPLIER } else { 1 };
+
+        if let Some(score) = scoreboard.players.get_mut(event.player) {
+            score.points += score_table.points(&event.target) * multiplier;
+        }
+    }
+}
+
+// Marks the in-game score and lives display
+#[derive(Component)]
+pub struct Hud;
+
+// Marks the countdown shown during a timed game
 #[derive(Component)]
-struct Scoreboard {
-    lives: i32,
-    points: i32,
+pub struct MatchClock;
+
+pub fn scoreboard_update(
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    scoreboard: Res<Scoreboard>,
+    match_timer: Res<MatchTimer>,
+    mut query: Query<&mut Text, (With<Hud>, Without<MatchClock>)>,
+    mut clock_query: Query<&mut Text, With<MatchClock>>,
+    ship_query: Query<(&Player, &Effects, &Weapons)>,
+) {
+    // Only label the lines with the player number when there's more than one
+    let multiplayer = scoreboard.players.len() > 1;
+
+    for mut text in clock_query.iter_mut() {
+        let remaining = match_timer.0.duration() - match_timer.0.elapsed();
+        let seconds = remaining.as_secs_f32().ceil() as u32;
+        text.sections[0].value = format!("{}:{:02}", seconds / 60, seconds % 60);
+    }
+
+    for mut text in query.iter_mut() {
+        for (id, (score, sections)) in scoreboard
+            .players
+            .iter()
+            .zip(text.sections.chunks_mut(3))
+            .enumerate()
+        {
+            let prefix = if multiplayer {
+                format!("P{} ", id + 1)
+            } else {
+                String::new()
+            };
+
+            if roster.versus {
+                sections[0].value = format!("{}FRAGS: {}\n", prefix, score.frags);
+                sections[1].value = String::new();
+            } else {
+                sections[0].value = format!("{}SCORE: {}\n", prefix, score.points);
+                sections[1].value = if mode.has_lives() {
+                    format!("{}LIVES: {}\n", prefix, score.lives.max(0))
+                } else {
+                    String::new()
+                };
+            }
+
+            // The weapon only once there's a choice of them, then any power-ups
+            let mut extras = Vec::new();
+            if let Some((_, effects, weapons)) =
+                ship_query.iter().find(|(player, _, _)| player.id == id)
+            {
+                if weapons.owned.len() > 1 {
+                    extras.push(weapons.current.name().to_string());
+                }
+                if !effects.0.is_empty() {
+                    extras.push(effects.describe());
+                }
+            }
+            sections[2].value = if extras.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}\n", prefix, extras.join("  "))
+            };
+        }
+    }
 }
 
-fn scoreboard_update(scoreboard: Res<Scoreboard>, mut query: Query<&mut Text>) {
-    let mut text = query.single_mut();
-    text.sections[0].value = format!("SCORE: {}\n", scoreboard.points);
-    text.sections[1].value = format!("LIVES: {}", scoreboard.lives);
+// Spawns the score and lives display, plus the clock if the game has a time limit
+pub fn spawn_hud(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    roster: &Roster,
+    time_limit: Option<f32>,
+) {
+    // A score, a lives and a weapon and power-ups line for each player, in their ship's colour
+    let sections = (0..roster.players)
+        .flat_map(|id| ["SCORE: ", "LIVES: ", ""].map(|label| (id, label)))
+        .map(|(id, label)| TextSection {
+            value: label.into(),
+            style: TextStyle {
+                font: font.clone(),
+                font_size: 40.0,
+                color: PLAYER_COLORS[id % PLAYER_COLORS.len()],
+            },
+        })
+        .collect();
+
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text {
+                sections,
+                ..Default::default()
+            },
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(12.0),
+                    right: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Hud);
+
+    if time_limit.is_some() {
+        commands
+            .spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(12.0),
+                        left: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Hud)
+            .insert(MatchClock);
+    }
 }
 
 #[derive(Component)]
@@ -327,119 +1384,641 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<PlayerDeathEvent>()
             .add_event::<BulletFireEvent>()
+            .add_event::<TargetDestroyed>()
+            .init_resource::<ScoreTables>()
+            .init_resource::<Roster>()
+            .init_resource::<VersusRules>()
+            .insert_resource(MatchTimer(Timer::from_seconds(0.0, false)))
             .insert_resource(Scoreboard {
-                points: 0,
-                lives: 3,
+                players: Vec::new(),
             })
+            .init_resource::<AsteroidPhysics>()
+            .init_resource::<DestroyedAsteroids>()
+            .init_resource::<RunStats>()
+            .init_resource::<GameAssets>()
+            .init_resource::<GameSeed>()
+            .init_resource::<Difficulty>()
+            .init_resource::<GameMode>()
+            .init_resource::<GameRng>()
+            .init_resource::<FrameTime>()
             .insert_resource(AsteroidTimer(Timer::from_seconds(2.0, true)))
-            .insert_resource(BulletFireTimer(Timer::from_seconds(0.3, true)))
-            .add_system_set(SystemSet::on_enter(GameState::Game).with_system(game_setup))
+            .add_system_to_stage(CoreStage::PreUpdate, update_frame_time)
+            .add_system_to_stage(CoreStage::Last, limit_frame_rate)
+            // Anything an earlier game left behind goes before the new one is set up
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(stop_game.label("clear_game"))
+                    .with_system(game_setup.after("clear_game")),
+            )
+            // Systems that touch the same things are always ordered, even where either
+            // order would do. Bevy picks an order for the rest afresh in every app,
+            // and replays, rollback and the leaderboard all count on a game
+            // playing out the same way in a new one.
             .add_system_set(
                 SystemSet::on_update(GameState::Game)
-                    .with_system(asteroid_movement)
-                    .with_system(asteroid_collision)
-                    .with_system(bullet_fire)
-                    .with_system(bullet_movement)
-                    .with_system(player_movement)
-                    .with_system(player_death)
-                    .with_system(spawn_asteroid)
-                    .with_system(scoreboard_update),
+                    // Other input systems come from other plugins, which order themselves before `Fire`
+                    .with_system(
+                        drive_ships
+                            .label(GameSystem::Input)
+                            .before(GameSystem::Fire),
+                    )
+                    .with_system(player_fire.label(GameSystem::Fire))
+                    .with_system(player_hyperspace.label(GameSystem::Fire))
+                    .with_system(
+                        bullet_fire
+                            .label(GameSystem::Spawn)
+                            .label("bullet_fire")
+                            .after(GameSystem::Fire),
+                    )
+                    .with_system(
+                        spawn_asteroid
+                            .label(GameSystem::Spawn)
+                            .after(GameSystem::Fire)
+                            .after("bullet_fire"),
+                    )
+                    // Missiles turn before anything moves, so they always chase where
+                    // their targets were at the start of the frame
+                    .with_system(
+                        steer_missiles
+                            .after(GameSystem::Spawn)
+                            .before(GameSystem::Movement),
+                    )
+                    .with_system(
+                        player_movement
+                            .label(GameSystem::Movement)
+                            .after(GameSystem::Spawn),
+                    )
+                    .with_system(
+                        asteroid_movement
+                            .label(GameSystem::Movement)
+                            .after(GameSystem::Spawn),
+                    )
+                    .with_system(
+                        power_up_movement
+                            .label(GameSystem::Movement)
+                            .after(GameSystem::Spawn),
+                    )
+                    .with_system(
+                        bullet_movement
+                            .label(GameSystem::Movement)
+                            .after(GameSystem::Spawn),
+                    )
+                    // Power-ups first, so a shield picked up just in time works
+                    .with_system(
+                        pick_up_power_ups
+                            .label(GameSystem::Collision)
+                            .label("pick_up_power_ups")
+                            .after(GameSystem::Movement),
+                    )
+                    .with_system(
+                        asteroid_collision
+                            .label(GameSystem::Collision)
+                            .label("asteroid_collision")
+                            .after(GameSystem::Movement)
+                            .after("pick_up_power_ups"),
+                    )
+                    // Bounces move asteroids, so they must not race the bullet and ship checks
+                    .with_system(
+                        asteroid_bounce
+                            .label(GameSystem::Collision)
+                            .after(GameSystem::Movement)
+                            .after("asteroid_collision"),
+                    )
+                    .with_system(
+                        bullet_collision
+                            .label(GameSystem::Collision)
+                            .after(GameSystem::Movement)
+                            .after("asteroid_collision"),
+                    )
+                    // Points scored as a ship dies still get its multiplier
+                    .with_system(
+                        player_death
+                            .label(GameSystem::Damage)
+                            .label("player_death")
+                            .after(GameSystem::Collision)
+                            .after("award_points"),
+                    )
+                    .with_system(
+                        match_end
+                            .label(GameSystem::Damage)
+                            .after(GameSystem::Collision)
+                            .after("player_death"),
+                    )
+                    .with_system(
+                        award_points
+                            .label(GameSystem::Damage)
+                            .label("award_points")
+                            .after(GameSystem::Collision),
+                    )
+                    .with_system(
+                        drop_power_ups
+                            .label(GameSystem::Damage)
+                            .after(GameSystem::Collision),
+                    )
+                    // After scoring, so a multiplier that runs out this frame still counts
+                    .with_system(
+                        tick_effects
+                            .label(GameSystem::Damage)
+                            .after(GameSystem::Collision)
+                            .after("award_points")
+                            .after("player_death"),
+                    )
+                    .with_system(
+                        scoreboard_update
+                            .label(GameSystem::Hud)
+                            .after(GameSystem::Damage),
+                    ),
             )
             .add_system_set(SystemSet::on_exit(GameState::Game).with_system(stop_game));
     }
 }
 
+// Resets everything a game keeps between frames, so each game starts the
+// same way whatever came before it
+#[allow(clippy::too_many_arguments)]
 fn game_setup(
     mut commands: Commands,
+    roster: Res<Roster>,
+    rules: Res<VersusRules>,
+    difficulty: Res<Difficulty>,
+    mode: Res<GameMode>,
+    seed: Res<GameSeed>,
+    mut rng: ResMut<GameRng>,
     mut scoreboard: ResMut<Scoreboard>,
-    asset_server: Res<AssetServer>,
+    mut stats: ResMut<RunStats>,
+    mut match_timer: ResMut<MatchTimer>,
+    mut asteroid_timer: ResMut<AsteroidTimer>,
+    assets: Res<GameAssets>,
 ) {
-    let player_texture = asset_server.load("player.png");
-    let font = asset_server.load("Orbitron.ttf");
-
-    scoreboard.points = 0;
-    scoreboard.lives = 3;
-
-    commands.spawn_bundle(TextBundle {
-        text: Text {
-            sections: vec![
-                TextSection {
-                    value: "SCORE: ".into(),
-                    style: TextStyle {
-                        font: font.clone(),
-                        font_size: 40.0,
-                        color: Color::WHITE,
-                    },
+    rng.0 = StdRng::seed_from_u64(seed.0);
+
+    let (asteroid_interval, lives) = if roster.versus {
+        (difficulty.asteroid_interval(), difficulty.lives())
+    } else {
+        (mode.asteroid_interval(*difficulty), mode.lives(*difficulty))
+    };
+    asteroid_timer.0 = Timer::from_seconds(asteroid_interval, true);
+
+    *stats = RunStats {
+        players: vec![PlayerStats::default(); roster.players],
+        asteroids_released: 0,
+        wave: 0,
+    };
+
+    scoreboard.players = (0..roster.players)
+        .map(|_| PlayerScore {
+            lives,
+            points: 0,
+            frags: 0,
+        })
+        .collect();
+
+    let time_limit = time_limit(&roster, &rules, *mode);
+    match_timer.0 = Timer::from_seconds(time_limit.unwrap_or(0.0), false);
+
+    spawn_hud(&mut commands, &assets.font, &roster, time_limit);
+
+    for id in 0..roster.players {
+        add_ship(
+            &mut commands,
+            &assets.player,
+            ShipSnapshot {
+                player: Player {
+                    id,
+                    velocity: Vec2::new(0.0, 0.0),
                 },
-                TextSection {
-                    value: "LIVES: ".into(),
-                    style: TextStyle {
-                        font: font.clone(),
-                        font_size: 40.0,
-                        color: Color::WHITE,
-                    },
+                transform: Transform {
+                    translation: spawn_point(id, &roster),
+                    rotation: Quat::from_rotation_z(PI / 2.0),
+                    scale: Vec3::new(1.0, 1.0, 0.0),
                 },
-            ],
-            ..Default::default()
-        },
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                top: Val::Px(12.0),
-                right: Val::Px(12.0),
-                ..Default::default()
+                input: ShipInput::default(),
+                weapons: Weapons::default(),
+                hyperspace: Hyperspace::new(),
+                effects: Effects::default(),
             },
-            ..Default::default()
-        },
-        ..Default::default()
-    });
+        );
+    }
+}
 
+fn add_ship(commands: &mut Commands, texture: &Handle<Image>, ship: ShipSnapshot) {
     commands
         .spawn_bundle(SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 0.0),
-                rotation: Quat::from_rotation_z(PI / 2.0),
-                scale: Vec3::new(1.0, 1.0, 0.0),
-            },
+            transform: ship.transform,
             sprite: Sprite {
-                color: Color::WHITE,
+                color: PLAYER_COLORS[ship.player.id % PLAYER_COLORS.len()],
                 ..Default::default()
             },
-            texture: player_texture,
+            texture: texture.clone(),
             ..Default::default()
         })
-        .insert(Player {
-            velocity: Vec2::new(0.0, 0.0),
-        })
+        .insert(ship.player)
+        .insert(ship.input)
+        .insert(ship.weapons)
+        .insert(ship.hyperspace)
+        .insert(ship.effects)
         .insert(Collider::Player);
 }
 
+// Everything spawned for a game
+type InGame = Or<(
+    With<Player>,
+    With<Asteroid>,
+    With<Bullet>,
+    With<PowerUp>,
+    With<Hud>,
+)>;
+
+// Removes everything a game spawned, along with any events it didn't get to
 fn stop_game(
     mut commands: Commands,
-    asteroid_query: Query<(Entity, &Asteroid)>,
-    player_query: Query<(Entity, &Player)>,
-    text_query: Query<(Entity, &Text)>,
-    bullet_query: Query<(Entity, &Bullet)>,
+    mut player_death_events: ResMut<Events<PlayerDeathEvent>>,
+    mut bullet_fire_events: ResMut<Events<BulletFireEvent>>,
+    mut target_destroyed_events: ResMut<Events<TargetDestroyed>>,
+    query: Query<Entity, InGame>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    player_death_events.clear();
+    bullet_fire_events.clear();
+    target_destroyed_events.clear();
+}
+
+// One ship, as stored in a `GameSnapshot`
+#[derive(Clone)]
+pub struct ShipSnapshot {
+    pub player: Player,
+    pub transform: Transform,
+    pub input: ShipInput,
+    weapons: Weapons,
+    hyperspace: Hyperspace,
+    effects: Effects,
+}
+
+// Everything that changes while a game is running, copied out of the world so
+// the game can be put back to exactly this point later. Entities are kept in
+// the order the world stores them, which decides the order systems meet them
+// in, so a restored game carries on exactly as the original would have.
+#[derive(Clone)]
+pub struct GameSnapshot {
+    pub ships: Vec<ShipSnapshot>,
+    pub asteroids: Vec<(Asteroid, Transform)>,
+    pub bullets: Vec<(Bullet, Transform)>,
+    pub power_ups: Vec<(PowerUp, Transform)>,
+    pub scoreboard: Scoreboard,
+    pub stats: RunStats,
+    pub match_timer: Timer,
+    asteroid_timer: Timer,
+    rng: StdRng,
+    // Whether the game had already ended
+    pub over: bool,
+}
+
+impl GameSnapshot {
+    pub fn save(world: &mut World) -> Self {
+        let ships = world
+            .query::<(
+                &Player,
+                &Transform,
+                &ShipInput,
+                &Weapons,
+                &Hyperspace,
+                &Effects,
+            )>()
+            .iter(world)
+            .map(
+                |(player, transform, input, weapons, hyperspace, effects)| ShipSnapshot {
+                    player: player.clone(),
+                    transform: *transform,
+                    input: *input,
+                    weapons: weapons.clone(),
+                    hyperspace: hyperspace.clone(),
+                    effects: effects.clone(),
+                },
+            )
+            .collect();
+
+        let asteroids = world
+            .query::<(&Asteroid, &Transform)>()
+            .iter(world)
+            .map(|(asteroid, transform)| (asteroid.clone(), *transform))
+            .collect();
+
+        let bullets = world
+            .query::<(&Bullet, &Transform)>()
+            .iter(world)
+            .map(|(bullet, transform)| (bullet.clone(), *transform))
+            .collect();
+
+        let power_ups = world
+            .query::<(&PowerUp, &Transform)>()
+            .iter(world)
+            .map(|(power_up, transform)| (power_up.clone(), *transform))
+            .collect();
+
+        GameSnapshot {
+            ships,
+            asteroids,
+            bullets,
+            power_ups,
+            scoreboard: world.get_resource::<Scoreboard>().unwrap().clone(),
+            stats: world.get_resource::<RunStats>().unwrap().clone(),
+            match_timer: world.get_resource::<MatchTimer>().unwrap().0.clone(),
+            asteroid_timer: world.get_resource::<AsteroidTimer>().unwrap().0.clone(),
+            rng: world.get_resource::<GameRng>().unwrap().0.clone(),
+            over: world
+                .get_resource::<State<GameState>>()
+                .is_some_and(|state| *state.current() != GameState::Game),
+        }
+    }
+
+    // Replaces the ships, asteroids, bullets and power-ups in the world with the ones in
+    // the snapshot and winds the timers and random numbers back. Whether the
+    // game is over is left alone. Everything is spawned in the order it was
+    // saved, so it's stored, and met by systems, in that order again.
+    pub fn restore(&self, world: &mut World) {
+        let stale: Vec<Entity> = world
+            .query_filtered::<Entity, Or<(
+                With<Player>,
+                With<Asteroid>,
+                With<Bullet>,
+                With<PowerUp>,
+            )>>()
+            .iter(world)
+            .collect();
+        for entity in stale {
+            world.despawn(entity);
+        }
+
+        let mut queue = CommandQueue::default();
+        {
+            let assets = world.get_resource::<GameAssets>().unwrap();
+            let mut commands = Commands::new(&mut queue, world);
+
+            for ship in self.ships.iter() {
+                add_ship(&mut commands, &assets.player, ship.clone());
+            }
+            for (asteroid, transform) in self.asteroids.iter() {
+                add_asteroid(
+                    &mut commands,
+                    &assets.asteroid,
+                    asteroid.clone(),
+                    *transform,
+                );
+            }
+            for (bullet, transform) in self.bullets.iter() {
+                add_bullet(&mut commands, bullet.clone(), *transform);
+            }
+            for (power_up, transform) in self.power_ups.iter() {
+                power_ups::add_power_up(&mut commands, power_up.clone(), transform.translation);
+            }
+        }
+        queue.apply(world);
+
+        world.insert_resource(self.scoreboard.clone());
+        world.insert_resource(MatchTimer(self.match_timer.clone()));
+        world.insert_resource(self.stats.clone());
+        world.insert_resource(AsteroidTimer(self.asteroid_timer.clone()));
+        world.insert_resource(GameRng(self.rng.clone()));
+    }
+
+    // A hash of the whole snapshot, for checking that two copies of a game
+    // haven't drifted apart. Entities are hashed in a fixed order, so it only
+    // depends on what's in the game and not on how it's stored.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        let mut ships: Vec<&ShipSnapshot> = self.ships.iter().collect();
+        ships.sort_by_key(|ship| ship.player.id);
+
+        let mut asteroids: Vec<&(Asteroid, Transform)> = self.asteroids.iter().collect();
+        asteroids.sort_by_key(|(asteroid, transform)| {
+            (
+                transform_key(transform),
+                asteroid.size,
+                asteroid.speed.to_bits(),
+            )
+        });
+
+        let mut bullets: Vec<&(Bullet, Transform)> = self.bullets.iter().collect();
+        bullets.sort_by_key(|(bullet, transform)| {
+            (
+                transform_key(transform),
+                bullet.owner,
+                bullet.speed.to_bits(),
+                bullet.weapon,
+                bullet.range.as_ref().map(Timer::elapsed),
+                bullet.scored,
+            )
+        });
+
+        let mut power_ups: Vec<&(PowerUp, Transform)> = self.power_ups.iter().collect();
+        power_ups.sort_by_key(|(power_up, transform)| {
+            (
+                transform_key(transform),
+                power_up.kind,
+                power_up.lifetime.elapsed(),
+            )
+        });
+
+        for ship in ships {
+            ship.player.id.hash(&mut hasher);
+            ship.player.velocity.x.to_bits().hash(&mut hasher);
+            ship.player.velocity.y.to_bits().hash(&mut hasher);
+            transform_key(&ship.transform).hash(&mut hasher);
+            ship.input.hash(&mut hasher);
+            ship.weapons.current.hash(&mut hasher);
+            ship.weapons.owned.hash(&mut hasher);
+            ship.weapons.cooldown.hash(&mut hasher);
+            ship.weapons.switch_held.hash(&mut hasher);
+            ship.hyperspace.cooldown.elapsed().hash(&mut hasher);
+            ship.hyperspace.button_held.hash(&mut hasher);
+            for (kind, timer) in ship.effects.0.iter() {
+                (kind, timer.elapsed()).hash(&mut hasher);
+            }
+        }
+        for (asteroid, transform) in asteroids {
+            transform_key(transform).hash(&mut hasher);
+            asteroid.size.hash(&mut hasher);
+            asteroid.speed.to_bits().hash(&mut hasher);
+        }
+        for (bullet, transform) in bullets {
+            transform_key(transform).hash(&mut hasher);
+            bullet.owner.hash(&mut hasher);
+            bullet.speed.to_bits().hash(&mut hasher);
+            bullet.weapon.hash(&mut hasher);
+            bullet.range.as_ref().map(Timer::elapsed).hash(&mut hasher);
+            bullet.scored.hash(&mut hasher);
+        }
+        for (power_up, transform) in power_ups {
+            transform_key(transform).hash(&mut hasher);
+            power_up.kind.hash(&mut hasher);
+            power_up.lifetime.elapsed().hash(&mut hasher);
+        }
+        for score in self.scoreboard.players.iter() {
+            (score.lives, score.points, score.frags).hash(&mut hasher);
+        }
+
+        self.match_timer.elapsed().hash(&mut hasher);
+        self.asteroid_timer.elapsed().hash(&mut hasher);
+        // Drawing from a copy shows where the generator is without moving it on
+        self.rng.clone().next_u64().hash(&mut hasher);
+        self.over.hash(&mut hasher);
+
+        hasher.finish()
+    }
+}
+
+// A sprite standing in for something in a `GameSnapshot` that's being played
+// somewhere else, e.g. in a rollback session
+#[derive(Component, Clone, Copy)]
+pub enum MirrorSprite {
+    Ship(usize),
+    Asteroid,
+    Bullet,
+    PowerUp(PowerUpKind),
+}
+
+// Makes the given sprites show the snapshot, moving the ones already there
+// and adding or removing sprites as things come and go. New sprites are
+// tagged with `marker`.
+pub fn mirror_snapshot<M: Component + Clone>(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    snapshot: &GameSnapshot,
+    sprites: impl Iterator<Item = (Entity, MirrorSprite)>,
+    marker: M,
 ) {
-    let (player, _) = player_query.single();
-    commands.entity(player).despawn_recursive();
+    let mut ships = Vec::new();
+    let mut asteroids = Vec::new();
+    let mut bullets = Vec::new();
+    let mut power_ups = Vec::new();
+    for (entity, sprite) in sprites {
+        match sprite {
+            MirrorSprite::Ship(id) => ships.push((entity, id)),
+            MirrorSprite::Asteroid => asteroids.push(entity),
+            MirrorSprite::Bullet => bullets.push(entity),
+            MirrorSprite::PowerUp(kind) => power_ups.push((entity, kind)),
+        }
+    }
+
+    for (entity, id) in ships.iter() {
+        if snapshot.ships.iter().all(|ship| ship.player.id != *id) {
+            commands.entity(*entity).despawn_recursive();
+        }
+    }
 
-    for (asteroid, _) in asteroid_query.iter() {
-        commands.entity(asteroid).despawn_recursive();
+    for ship in snapshot.ships.iter() {
+        match ships.iter().find(|(_, id)| *id == ship.player.id) {
+            Some((entity, _)) => {
+                commands.entity(*entity).insert(ship.transform);
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        transform: ship.transform,
+                        sprite: Sprite {
+                            color: PLAYER_COLORS[ship.player.id % PLAYER_COLORS.len()],
+                            ..Default::default()
+                        },
+                        texture: assets.player.clone(),
+                        ..Default::default()
+                    })
+                    .insert(MirrorSprite::Ship(ship.player.id))
+                    .insert(marker.clone());
+            }
+        }
+    }
+
+    // Asteroids and bullets have nothing to tell them apart, so the sprites
+    // are reused in whatever order the snapshot lists them
+    let mut asteroids = asteroids.into_iter();
+    for (_, transform) in snapshot.asteroids.iter() {
+        match asteroids.next() {
+            Some(entity) => {
+                commands.entity(entity).insert(*transform);
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        transform: *transform,
+                        texture: assets.asteroid.clone(),
+                        ..Default::default()
+                    })
+                    .insert(MirrorSprite::Asteroid)
+                    .insert(marker.clone());
+            }
+        }
+    }
+    for entity in asteroids {
+        commands.entity(entity).despawn_recursive();
     }
 
-    for (text, _) in text_query.iter() {
-        commands.entity(text).despawn_recursive();
+    let mut bullets = bullets.into_iter();
+    for (bullet, transform) in snapshot.bullets.iter() {
+        let sprite = Sprite {
+            color: PLAYER_COLORS[bullet.owner % PLAYER_COLORS.len()],
+            ..Default::default()
+        };
+
+        match bullets.next() {
+            Some(entity) => {
+                commands.entity(entity).insert(*transform).insert(sprite);
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        transform: *transform,
+                        sprite,
+                        ..Default::default()
+                    })
+                    .insert(MirrorSprite::Bullet)
+                    .insert(marker.clone());
+            }
+        }
+    }
+    for entity in bullets {
+        commands.entity(entity).despawn_recursive();
     }
 
-    for (bullet, _) in bullet_query.iter() {
-        commands.entity(bullet).despawn_recursive();
+    // Power-ups only differ in their kind, so any sprite of the right kind will do
+    for (power_up, transform) in snapshot.power_ups.iter() {
+        match power_ups
+            .iter()
+            .position(|(_, kind)| *kind == power_up.kind)
+        {
+            Some(index) => {
+                let (entity, _) = power_ups.swap_remove(index);
+                commands.entity(entity).insert(*transform);
+            }
+            None => {
+                commands
+                    .spawn_bundle(power_up_sprite(power_up.kind, transform.translation))
+                    .insert(MirrorSprite::PowerUp(power_up.kind))
+                    .insert(marker.clone());
+            }
+        }
     }
+    for (entity, _) in power_ups {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Position and heading as exact bit patterns, for sorting and hashing
+fn transform_key(transform: &Transform) -> [u32; 4] {
+    [
+        transform.translation.x.to_bits(),
+        transform.translation.y.to_bits(),
+        transform.rotation.z.to_bits(),
+        transform.rotation.w.to_bits(),
+    ]
 }
 
 // Wraps a position to the other side of the screen if the position is over the edge.
 #[inline(always)]
-fn wrap_position(translation: &mut Vec3) {
+pub(crate) fn wrap_position(translation: &mut Vec3) {
     if translation.x < -640.0 {
         translation.x = 640.0;
     } else if translation.x > 640.0 {
@@ -454,7 +2033,7 @@ fn wrap_position(translation: &mut Vec3) {
 }
 
 // Converts a quaternion to an angle about the z axis
-fn quat_to_angle(rotation: &Quat) -> f32 {
+pub fn quat_to_angle(rotation: &Quat) -> f32 {
     let (v, angle) = rotation.to_axis_angle();
     v.z * angle
 }
@@ -1,29 +1,134 @@
-use super::{despawn_screen, GameState};
+use crate::challenge::{self, Challenge};
+use crate::game::{
+    Difficulty, GameMode, GameSeed, PlayerStats, Roster, RunStats, Scoreboard, PLAYER_COLORS,
+};
+use crate::high_scores::NewHighScore;
+use crate::{despawn_screen, GameState};
 use bevy::prelude::*;
 
 pub struct GameOverPlugin;
 
 impl Plugin for GameOverPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(game_over_setup))
-            .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(start_playing))
-            .add_system_set(
-                SystemSet::on_exit(GameState::GameOver)
-                    .with_system(despawn_screen::<GameOverScreen>),
-            );
+        app.add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(game_over_setup.after("record_high_score")),
+        )
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(start_playing))
+        .add_system_set(
+            SystemSet::on_exit(GameState::GameOver).with_system(despawn_screen::<GameOverScreen>),
+        );
     }
 }
 
-fn start_playing(mut game_state: ResMut<State<GameState>>, keys: Res<Input<KeyCode>>) {
+// Back to the menu, or straight into another game with the same players.
+// Only games played on this machine, which have a `GameSeed`, can restart.
+fn start_playing(
+    mut game_state: ResMut<State<GameState>>,
+    seed: Option<ResMut<GameSeed>>,
+    keys: Res<Input<KeyCode>>,
+) {
     if keys.just_released(KeyCode::Escape) {
         game_state.set(GameState::Menu).unwrap();
+        return;
+    }
+
+    let mut seed = match seed {
+        Some(seed) => seed,
+        None => return,
+    };
+
+    // Retrying replays the same seed, for practice
+    if keys.just_released(KeyCode::R) {
+        game_state.set(GameState::Game).unwrap();
+    } else if keys.just_released(KeyCode::N) {
+        *seed = GameSeed::random();
+        game_state.set(GameState::Game).unwrap();
     }
 }
 
 #[derive(Component)]
 struct GameOverScreen;
 
-fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+// The headline for the results screen. Versus matches name the winner,
+// unless the top of the table is shared.
+fn results_title(roster: &Roster, scoreboard: &Scoreboard) -> String {
+    if !roster.versus {
+        return "GAME OVER".into();
+    }
+
+    let best = scoreboard.players.iter().map(|score| score.frags).max();
+    let mut leaders = scoreboard
+        .players
+        .iter()
+        .enumerate()
+        .filter(|(_, score)| Some(score.frags) == best);
+
+    match (leaders.next(), leaders.next()) {
+        (Some((id, _)), None) => format!("PLAYER {} WINS", id + 1),
+        _ => "DRAW".into(),
+    }
+}
+
+// One line per player, best first
+fn results_lines(roster: &Roster, scoreboard: &Scoreboard) -> Vec<(usize, String)> {
+    let mut ranking: Vec<_> = scoreboard.players.iter().enumerate().collect();
+
+    if roster.versus {
+        ranking.sort_by_key(|(_, score)| -score.frags);
+    } else {
+        ranking.sort_by_key(|(_, score)| -score.points);
+    }
+
+    ranking
+        .into_iter()
+        .map(|(id, score)| {
+            let line = if roster.versus {
+                format!("P{}  FRAGS: {}", id + 1, score.frags)
+            } else if scoreboard.players.len() > 1 {
+                format!("P{}  SCORE: {}", id + 1, score.points)
+            } else {
+                format!("SCORE: {}", score.points)
+            };
+            (id, line)
+        })
+        .collect()
+}
+
+// The run summary under a player's result
+fn stats_text(stats: &PlayerStats) -> String {
+    let [small, medium, large] = stats.asteroids_destroyed;
+    let seconds = stats.time_survived.as_secs();
+
+    format!(
+        "ASTEROIDS  LARGE {}  MEDIUM {}  SMALL {}\n\
+         SHOTS {}  ACCURACY {:.0}%\n\
+         TIME {}:{:02}  BEST COMBO {}",
+        large,
+        medium,
+        small,
+        stats.shots_fired,
+        stats.accuracy() * 100.0,
+        seconds / 60,
+        seconds % 60,
+        stats.best_combo
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn game_over_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    scoreboard: Res<Scoreboard>,
+    // Missing, or empty, when the game was played somewhere that doesn't keep them
+    stats: Option<Res<RunStats>>,
+    seed: Option<Res<GameSeed>>,
+    new_high_score: Option<Res<NewHighScore>>,
+) {
+    let stats = stats.as_deref().filter(|stats| !stats.players.is_empty());
     let font = asset_server.load("Orbitron.ttf");
 
     commands
@@ -45,7 +150,7 @@ fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     ..Default::default()
                 },
                 text: Text::with_section(
-                    "GAME OVER",
+                    results_title(&roster, &scoreboard),
                     TextStyle {
                         font: font.clone(),
                         font_size: 80.0,
@@ -56,13 +161,132 @@ fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..Default::default()
             });
 
+            for (id, line) in results_lines(&roster, &scoreboard) {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        line,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 40.0,
+                            color: PLAYER_COLORS[id % PLAYER_COLORS.len()],
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+
+                if let Some(player_stats) = stats.and_then(|stats| stats.players.get(id)) {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            stats_text(player_stats),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 20.0,
+                                color: Color::GRAY,
+                            },
+                            TextAlignment {
+                                horizontal: HorizontalAlign::Center,
+                                ..Default::default()
+                            },
+                        ),
+                        ..Default::default()
+                    });
+                }
+            }
+
+            if let Some(stats) = stats.filter(|_| !roster.versus) {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        format!("{}  WAVE REACHED: {}", mode.name(), stats.wave.max(1)),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+
+            // The code to race this run, for anyone who wants to
+            if let Some(seed) = seed.as_ref().filter(|_| !roster.versus) {
+                let challenge = Challenge {
+                    seed: seed.0,
+                    mode: *mode,
+                    difficulty: *difficulty,
+                };
+                let today = challenge::today();
+                let line = if challenge == Challenge::daily(today) {
+                    format!(
+                        "DAILY CHALLENGE {}  CODE: {}",
+                        challenge::date_text(today),
+                        challenge.code()
+                    )
+                } else {
+                    format!("SEED CODE: {}", challenge.code())
+                };
+
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        line,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+
+            if let Some(place) = new_high_score.and_then(|new_high_score| new_high_score.0) {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        format!("NEW HIGH SCORE! #{} IN {}", place + 1, mode.name()),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 32.0,
+                            color: Color::GOLD,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+
             parent.spawn_bundle(TextBundle {
                 style: Style {
                     margin: Rect::all(Val::Px(50.0)),
                     ..Default::default()
                 },
                 text: Text::with_section(
-                    "PRESS [ESC] TO RETURN TO MENU",
+                    if seed.is_some() {
+                        "PRESS [R] RETRY\nPRESS [N] NEW GAME\nPRESS [ESC] MENU"
+                    } else {
+                        "PRESS [ESC] TO RETURN TO MENU"
+                    },
                     TextStyle {
                         font: font.clone(),
                         font_size: 40.0,
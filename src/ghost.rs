@@ -0,0 +1,234 @@
+// Racing a previous best. In time attack and the daily challenge, the best
+// saved replay of the same seed plays alongside the game as see-through ships
+// that nothing can hit, with its score next to the player's, so they can see
+// where they're ahead or behind. It can be turned off in the settings.
+
+use std::time::Duration;
+
+use crate::challenge::{self, Challenge};
+use crate::game::{
+    AsteroidPhysics, Difficulty, FrameTime, GameAssets, GameMode, GameSeed, GameSystem, Roster,
+    Scoreboard, PLAYER_COLORS,
+};
+use crate::replay::{self, Replay, ReplayPlayer};
+use crate::settings::Settings;
+use crate::{despawn_screen, GameState};
+use bevy::prelude::*;
+
+// How much of a ghost ship shows through
+const GHOST_ALPHA: f32 = 0.3;
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        // The player holds a whole bevy app, which can't be shared between threads
+        app.insert_non_send_resource(Ghost {
+            player: None,
+            owed: Duration::ZERO,
+        })
+        .add_system_set(SystemSet::on_enter(GameState::Game).with_system(start_ghost))
+        .add_system_set(
+            SystemSet::on_update(GameState::Game).with_system(race_ghost.after(GameSystem::Damage)),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Game)
+                .with_system(stop_ghost)
+                .with_system(despawn_screen::<GhostEntity>),
+        );
+    }
+}
+
+struct Ghost {
+    player: Option<ReplayPlayer>,
+    // Game time the ghost is behind the game
+    owed: Duration,
+}
+
+// A ghost ship, by player id
+#[derive(Component)]
+struct GhostShip(usize);
+
+// Anything drawn for the ghost, removed when the game ends
+#[derive(Component)]
+struct GhostEntity;
+
+#[derive(Component)]
+struct GhostText;
+
+// The best run saved for a game set up like this one
+fn best_run(
+    seed: u64,
+    mode: GameMode,
+    difficulty: Difficulty,
+    roster: &Roster,
+    physics: bool,
+) -> Option<Replay> {
+    replay::list()
+        .iter()
+        .filter_map(|path| Replay::load(path).ok())
+        .filter(|replay| {
+            replay.seed == seed
+                && replay.mode == mode
+                && replay.difficulty == difficulty
+                && replay.roster.players == roster.players
+                && !replay.roster.versus
+                && replay.asteroid_physics == physics
+        })
+        // Ranked the way the high score tables are
+        .max_by_key(|replay| {
+            if mode == GameMode::Survival {
+                (replay.duration(), replay.points())
+            } else {
+                (Duration::ZERO, replay.points())
+            }
+        })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_ghost(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    seed: Res<GameSeed>,
+    physics: Res<AsteroidPhysics>,
+    mut ghost: NonSendMut<Ghost>,
+) {
+    ghost.player = None;
+    ghost.owed = Duration::ZERO;
+
+    let challenge = Challenge {
+        seed: seed.0,
+        mode: *mode,
+        difficulty: *difficulty,
+    };
+    let raced = *mode == GameMode::TimeAttack || challenge == Challenge::daily(challenge::today());
+    if !settings.ghost || roster.versus || !raced {
+        return;
+    }
+
+    let best = best_run(seed.0, *mode, *difficulty, &roster, physics.enabled);
+    ghost.player = best.map(ReplayPlayer::new);
+    if ghost.player.is_none() {
+        return;
+    }
+
+    // Filled in by `race_ghost`
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: assets.font.clone(),
+                    font_size: 28.0 * settings.hud_scale,
+                    color: Color::rgba(1.0, 1.0, 1.0, 0.6),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(12.0),
+                    right: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(GhostText)
+        .insert(GhostEntity);
+}
+
+fn stop_ghost(mut ghost: NonSendMut<Ghost>) {
+    ghost.player = None;
+}
+
+// Keeps the ghost level with the game's clock and draws where it's got to
+fn race_ghost(
+    mut commands: Commands,
+    time: Res<FrameTime>,
+    assets: Res<GameAssets>,
+    scoreboard: Res<Scoreboard>,
+    mut ghost: NonSendMut<Ghost>,
+    mut ship_query: Query<(Entity, &GhostShip, &mut Transform)>,
+    mut text_query: Query<&mut Text, With<GhostText>>,
+) {
+    let Ghost { player, owed } = &mut *ghost;
+    let player = match player {
+        Some(player) => player,
+        None => return,
+    };
+
+    *owed += time.delta;
+    while let Some(delta) = player.next_delta() {
+        if *owed < delta {
+            break;
+        }
+
+        *owed -= delta;
+        player.step();
+    }
+
+    let snapshot = player.snapshot();
+
+    for (entity, ship, mut transform) in ship_query.iter_mut() {
+        match snapshot
+            .ships
+            .iter()
+            .find(|ghost| ghost.player.id == ship.0)
+        {
+            Some(ghost) => *transform = ghost.transform,
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for ghost in snapshot.ships.iter() {
+        let id = ghost.player.id;
+        if ship_query.iter().any(|(_, ship, _)| ship.0 == id) {
+            continue;
+        }
+
+        let mut color = PLAYER_COLORS[id % PLAYER_COLORS.len()];
+        color.set_a(GHOST_ALPHA);
+
+        // No collider, so it's never hit and never hits anything
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: ghost.transform,
+                sprite: Sprite {
+                    color,
+                    ..Default::default()
+                },
+                texture: assets.player.clone(),
+                ..Default::default()
+            })
+            .insert(GhostShip(id))
+            .insert(GhostEntity);
+    }
+
+    let points: i32 = scoreboard.players.iter().map(|score| score.points).sum();
+    let ghost_points: i32 = snapshot
+        .scoreboard
+        .players
+        .iter()
+        .map(|score| score.points)
+        .sum();
+    let line = format!(
+        "GHOST: {}  {:+}{}",
+        ghost_points,
+        points - ghost_points,
+        if player.is_finished() {
+            "  (FINISHED)"
+        } else {
+            ""
+        }
+    );
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = line.clone();
+    }
+}
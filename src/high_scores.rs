@@ -0,0 +1,138 @@
+// The best runs in each game mode, kept in a file between sessions. Every
+// local game that isn't versus is entered when it ends. Co-op teams go in
+// together, with their points added up.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::game::{GameMode, GameSeed, Roster, RunStats, Scoreboard};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const HIGH_SCORES_FILE: &str = "high_scores.json";
+
+// Entries kept per mode
+const TABLE_SIZE: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub points: i32,
+    // How long the longest lasting ship was in play
+    pub seconds: f32,
+    pub wave: u32,
+    pub players: usize,
+    // What the game was seeded with, so it can be played again
+    pub seed: u64,
+}
+
+impl HighScore {
+    // Survival runs are ranked by how long they lasted, everything else by points
+    fn beats(&self, other: &HighScore, mode: GameMode) -> bool {
+        if mode == GameMode::Survival {
+            (self.seconds, self.points) > (other.seconds, other.points)
+        } else {
+            (self.points, self.seconds) > (other.points, other.seconds)
+        }
+    }
+}
+
+// Each mode's table, best first
+#[derive(Default, Serialize, Deserialize)]
+pub struct HighScores(BTreeMap<GameMode, Vec<HighScore>>);
+
+impl HighScores {
+    // The saved tables, or empty ones if there aren't any yet or they can't
+    // be read
+    pub fn load() -> Self {
+        match fs::read_to_string(HIGH_SCORES_FILE) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|error| {
+                warn!("ignoring unreadable {}: {}", HIGH_SCORES_FILE, error);
+                HighScores::default()
+            }),
+            Err(_) => HighScores::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|text| fs::write(HIGH_SCORES_FILE, text).map_err(|error| error.to_string()));
+
+        if let Err(error) = result {
+            warn!("failed to save {}: {}", HIGH_SCORES_FILE, error);
+        }
+    }
+
+    pub fn table(&self, mode: GameMode) -> &[HighScore] {
+        self.0.get(&mode).map_or(&[], |table| table.as_slice())
+    }
+
+    // Enters the score in its mode's table if it's good enough, and returns
+    // its place counting from zero
+    pub fn record(&mut self, mode: GameMode, score: HighScore) -> Option<usize> {
+        let table = self.0.entry(mode).or_default();
+        let place = table
+            .iter()
+            .position(|entry| score.beats(entry, mode))
+            .unwrap_or(table.len());
+
+        if place >= TABLE_SIZE {
+            return None;
+        }
+
+        table.insert(place, score);
+        table.truncate(TABLE_SIZE);
+        Some(place)
+    }
+}
+
+// Where the last game placed in its table, if it got in
+#[derive(Default)]
+pub struct NewHighScore(pub Option<usize>);
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load())
+            .init_resource::<NewHighScore>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(record_high_score.label("record_high_score")),
+            );
+    }
+}
+
+fn record_high_score(
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    scoreboard: Res<Scoreboard>,
+    stats: Res<RunStats>,
+    seed: Res<GameSeed>,
+    mut high_scores: ResMut<HighScores>,
+    mut new_high_score: ResMut<NewHighScore>,
+) {
+    new_high_score.0 = None;
+
+    if roster.versus {
+        return;
+    }
+
+    let score = HighScore {
+        points: scoreboard.players.iter().map(|score| score.points).sum(),
+        seconds: stats
+            .players
+            .iter()
+            .map(|stats| stats.time_survived.as_secs_f32())
+            .fold(0.0, f32::max),
+        wave: stats.wave,
+        players: roster.players,
+        seed: seed.0,
+    };
+
+    new_high_score.0 = high_scores.record(*mode, score);
+    if new_high_score.0.is_some() {
+        high_scores.save();
+    }
+}
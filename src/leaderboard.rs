@@ -0,0 +1,231 @@
+// Tournament leaderboards, kept by `asteroids-leaderboard`. A game only counts
+// once the server has played its replay again and got the same result, so a
+// score can't simply be made up. A server started with a key also wants every
+// submission signed with it, so only the tournament's own machines can enter.
+//
+//     POST /scores
+//         A replay file as the body, the player's name in `X-Player-Name`
+//         and, for a server with a key, the body's HMAC-SHA256 in hex in
+//         `X-Signature`. Answered with a `SubmitReply`.
+//
+//     GET /scores?mode=time-attack[&seed=1234]
+//         The best runs in a mode, or on one seed of it, as a list of
+//         `LeaderboardEntry`.
+//
+// The game sends every finished game that isn't versus to the server at
+// `leaderboard_url` in the settings file, and the high score screen shows the
+// server's tables.
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::game::GameMode;
+use crate::replay::LastReplay;
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+pub const NAME_HEADER: &str = "X-Player-Name";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+// Entries served per table
+pub const TABLE_SIZE: usize = 10;
+
+// Replays are played again before the server answers, which can take a while
+const REQUEST_TIMEOUT_SECONDS: u64 = 60;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub points: i32,
+    // How long the longest lasting ship was in play
+    pub seconds: f32,
+    pub wave: u32,
+    pub players: usize,
+    pub seed: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SubmitReply {
+    // Where the game placed in its mode's table, counting from one, if it
+    // was accepted
+    pub rank: Option<usize>,
+    // Why it wasn't
+    pub errors: Vec<String>,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+// The signature a server with this key wants on a submission
+pub fn sign(key: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC takes any key");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn signature_matches(key: &str, body: &[u8], signature: &str) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC takes any key");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+        .build()
+}
+
+fn scores_url(url: &str) -> String {
+    format!("{}/scores", url.trim_end_matches('/'))
+}
+
+fn submit(url: &str, name: &str, key: Option<&str>, body: &[u8]) -> Result<SubmitReply, String> {
+    let mut request = agent().post(&scores_url(url)).set(NAME_HEADER, name);
+    if let Some(key) = key {
+        request = request.set(SIGNATURE_HEADER, &sign(key, body));
+    }
+
+    // Turned down submissions still come with a reply saying why
+    let response = match request.send_bytes(body) {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(error) => return Err(error.to_string()),
+    };
+    serde_json::from_reader(response.into_reader()).map_err(|error| error.to_string())
+}
+
+fn fetch_table(url: &str, mode: GameMode) -> Result<Vec<LeaderboardEntry>, String> {
+    let response = agent()
+        .get(&scores_url(url))
+        .query("mode", mode.key())
+        .call()
+        .map_err(|error| error.to_string())?;
+    serde_json::from_reader(response.into_reader()).map_err(|error| error.to_string())
+}
+
+// Something asked of the server, and then its answer
+pub enum Request<T> {
+    Waiting(Receiver<Result<T, String>>),
+    Done(Result<T, String>),
+}
+
+impl<T: Send + 'static> Request<T> {
+    // Asks on a thread of its own, so the game carries on meanwhile
+    fn send(ask: impl FnOnce() -> Result<T, String> + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(ask());
+        });
+
+        Request::Waiting(receiver)
+    }
+
+    fn poll(&mut self) {
+        if let Request::Waiting(receiver) = self {
+            match receiver.try_recv() {
+                Ok(result) => *self = Request::Done(result),
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    *self = Request::Done(Err("no answer".into()))
+                }
+            }
+        }
+    }
+
+    // The answer, once there is one
+    pub fn answer(&self) -> Option<&Result<T, String>> {
+        match self {
+            Request::Waiting(_) => None,
+            Request::Done(result) => Some(result),
+        }
+    }
+}
+
+// The game's dealings with the leaderboard server
+#[derive(Default)]
+pub struct Leaderboard {
+    // The last game sent in, and its mode
+    pub submission: Option<(GameMode, Request<SubmitReply>)>,
+    // The table last asked for
+    pub table: Option<(GameMode, Request<Vec<LeaderboardEntry>>)>,
+}
+
+impl Leaderboard {
+    // Asks for a mode's table, unless it's already been asked for
+    pub fn fetch(&mut self, url: &str, mode: GameMode) {
+        if matches!(&self.table, Some((shown, _)) if *shown == mode) {
+            return;
+        }
+
+        let url = url.to_string();
+        self.table = Some((mode, Request::send(move || fetch_table(&url, mode))));
+    }
+}
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        // Waiting on other threads isn't something to share between threads
+        app.insert_non_send_resource(Leaderboard::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(submit_game.after("save_replay")),
+            )
+            .add_system(poll_leaderboard);
+    }
+}
+
+fn submit_game(
+    settings: Res<Settings>,
+    last: Res<LastReplay>,
+    mut leaderboard: NonSendMut<Leaderboard>,
+) {
+    let (url, replay) = match (&settings.leaderboard_url, &last.0) {
+        (Some(url), Some(replay)) if !replay.roster.versus => (url.clone(), replay),
+        _ => return,
+    };
+
+    let body = match replay.to_bytes() {
+        Ok(body) => body,
+        Err(error) => {
+            warn!("can't send game to the leaderboard: {}", error);
+            return;
+        }
+    };
+    let name = settings.player_name.clone();
+    let key = settings.leaderboard_key.clone();
+
+    leaderboard.submission = Some((
+        replay.mode,
+        Request::send(move || submit(&url, &name, key.as_deref(), &body)),
+    ));
+}
+
+fn poll_leaderboard(mut leaderboard: NonSendMut<Leaderboard>) {
+    let leaderboard = &mut *leaderboard;
+
+    if let Some((_, submission)) = &mut leaderboard.submission {
+        let waiting = submission.answer().is_none();
+        submission.poll();
+
+        // The game just entered may have moved the table on, so it's
+        // fetched again when next wanted
+        if waiting && submission.answer().is_some() {
+            leaderboard.table = None;
+        }
+    }
+    if let Some((_, table)) = &mut leaderboard.table {
+        table.poll();
+    }
+}
@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+
+pub mod attract;
+pub mod buttons;
+pub mod challenge;
+pub mod controls;
+pub mod env;
+pub mod game;
+pub mod game_over;
+pub mod ghost;
+pub mod high_scores;
+pub mod leaderboard;
+pub mod menu;
+pub mod net;
+pub mod pilot;
+pub mod power_ups;
+pub mod remote;
+pub mod replay;
+pub mod replay_viewer;
+pub mod rollback;
+pub mod settings;
+pub mod weapons;
+
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum GameState {
+    Game,
+    Menu,
+    GameOver,
+    Settings,
+    Modes,
+    HighScores,
+    Controls,
+    Credits,
+    EnterCode,
+    Replays,
+    Playback,
+    // Pushed on top of `Game` to hold it for a while, e.g. for remote bots
+    Paused,
+}
+
+// Generic system that takes a component as a parameter, and will despawn all entities with that component
+pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
+    for entity in to_despawn.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,43 +1,188 @@
+// Runs the game locally, joins a dedicated server with
+//
+//     asteroids --connect 127.0.0.1:7777
+//
+// or plays a 1v1 match straight against another player with
+//
+//     asteroids --peer 192.168.1.20:7777 [--bind 0.0.0.0:7777]
+//
+// In a local game, `--bot 2` hands player 2's ship to the built-in autopilot,
+// and `--remote-bot 1` hands player 1's to a bot connecting over TCP, see
+// `remote.rs`. Add `--lockstep` to have the game wait for remote bots.
+//
+//     asteroids verify replays/1760000000-classic.replay
+//
+// plays a saved replay again without opening a window and checks that it
+// ends with the score, lives and wave it claims. It exits with 1 if it
+// doesn't, or 2 if the replay can't be read.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use asteroids::controls::Bots;
+use asteroids::net::client::NetClientPlugin;
+use asteroids::net::DEFAULT_PORT;
+use asteroids::remote::{self, RemotePlugin};
+use asteroids::replay::{Replay, ReplayPlugin};
+use asteroids::replay_viewer::ReplayViewerPlugin;
+use asteroids::rollback::peer::RollbackPeerPlugin;
+use asteroids::settings::{Settings, SettingsPlugin};
+use asteroids::{
+    attract, controls, game, game_over, ghost, high_scores, leaderboard, menu, GameState,
+};
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
 
-mod game;
-mod game_over;
-mod menu;
+fn main() {
+    if std::env::args().nth(1).as_deref() == Some("verify") {
+        std::process::exit(verify(std::env::args().nth(2)));
+    }
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
-enum GameState {
-    Game,
-    Menu,
-    GameOver,
-}
+    let mut server = None;
+    let mut peer = None;
+    let mut bots = Vec::new();
+    let mut remote_bots = Vec::new();
+    let mut lockstep = false;
+    let mut bind = SocketAddr::from(([0, 0, 0, 0], DEFAULT_PORT));
 
-fn main() {
-    App::new()
-        .insert_resource(WindowDescriptor {
-            title: "Asteroids!".into(),
-            ..Default::default()
-        })
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--connect" => {
+                server = Some(
+                    args.next()
+                        .and_then(|addr| addr.parse::<SocketAddr>().ok())
+                        .expect("--connect needs an address like 127.0.0.1:7777"),
+                )
+            }
+            "--peer" => {
+                peer = Some(
+                    args.next()
+                        .and_then(|addr| addr.parse::<SocketAddr>().ok())
+                        .expect("--peer needs an address like 192.168.1.20:7777"),
+                )
+            }
+            "--bind" => {
+                bind = args
+                    .next()
+                    .and_then(|addr| addr.parse().ok())
+                    .expect("--bind needs an address like 0.0.0.0:7777")
+            }
+            "--bot" => bots.push(
+                args.next()
+                    .and_then(|player| player.parse::<usize>().ok())
+                    .filter(|player| (1..=4).contains(player))
+                    .expect("--bot needs a player number from 1 to 4")
+                    - 1,
+            ),
+            "--remote-bot" => remote_bots.push(
+                args.next()
+                    .and_then(|player| player.parse::<usize>().ok())
+                    .filter(|player| (1..=4).contains(player))
+                    .expect("--remote-bot needs a player number from 1 to 4")
+                    - 1,
+            ),
+            "--lockstep" => lockstep = true,
+            _ => {
+                eprintln!(
+                    "usage: asteroids [--bot PLAYER]... [--remote-bot PLAYER]... [--lockstep] \
+                     [--connect ADDRESS | --peer ADDRESS [--bind ADDRESS]]\n       \
+                     asteroids verify REPLAY"
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // The window opens with the saved settings rather than resizing after
+    let settings = Settings::load();
+
+    let mut app = App::new();
+    app.insert_resource(settings.window_descriptor())
+        .insert_resource(settings)
         .add_plugins(DefaultPlugins)
-        .add_plugin(menu::MenuPlugin)
-        .add_plugin(game::GamePlugin)
-        .add_plugin(game_over::GameOverPlugin)
+        .add_plugin(SettingsPlugin);
+
+    match (server, peer) {
+        // The server runs the game, we only draw it
+        (Some(server), _) => {
+            app.add_plugin(NetClientPlugin { server });
+        }
+        (None, Some(peer)) => {
+            app.add_plugin(RollbackPeerPlugin { bind, peer });
+        }
+        (None, None) => {
+            app.insert_resource(Bots(bots))
+                .add_plugin(menu::MenuPlugin)
+                .add_plugin(attract::AttractPlugin)
+                .add_plugin(game::GamePlugin)
+                .add_plugin(high_scores::HighScoresPlugin)
+                .add_plugin(leaderboard::LeaderboardPlugin)
+                .add_plugin(ReplayPlugin)
+                .add_plugin(ReplayViewerPlugin)
+                .add_plugin(ghost::GhostPlugin)
+                .add_plugin(controls::ControlsPlugin);
+
+            if !remote_bots.is_empty() {
+                app.add_plugin(RemotePlugin {
+                    address: SocketAddr::from(([127, 0, 0, 1], remote::DEFAULT_PORT)),
+                    players: remote_bots,
+                    lockstep,
+                });
+            }
+        }
+    }
+
+    app.add_plugin(game_over::GameOverPlugin)
         .add_plugin(LogDiagnosticsPlugin::default())
-        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin)
         .insert_resource(ClearColor(Color::BLACK))
         .add_startup_system(setup)
         .add_state(GameState::Menu)
         .run();
 }
 
+// Checks a replay for `asteroids verify` and returns the exit code
+fn verify(path: Option<String>) -> i32 {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("usage: asteroids verify REPLAY");
+            return 2;
+        }
+    };
+
+    let replay = match Replay::load(&path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("can't read {}: {}", path.display(), error);
+            return 2;
+        }
+    };
+
+    println!(
+        "{}: {} frames, claims {} points by wave {}",
+        path.display(),
+        replay.frames.len(),
+        replay.points(),
+        replay.wave
+    );
+
+    match replay.verify() {
+        Ok(_) => {
+            println!("verified");
+            0
+        }
+        Err(mismatches) => {
+            for mismatch in mismatches {
+                println!("mismatch: {}", mismatch);
+            }
+            1
+        }
+    }
+}
+
 fn setup(mut commands: Commands) {
     commands.spawn_bundle(UiCameraBundle::default());
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
 }
-
-// Generic system that takes a component as a parameter, and will despawn all entities with that component
-fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
-    for entity in to_despawn.iter() {
-        commands.entity(entity).despawn_recursive();
-    }
-}
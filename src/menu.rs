@@ -1,28 +1,533 @@
-use super::{despawn_screen, GameState};
+// The title screen and the pages it leads to. Every page is a list of buttons
+// from `buttons.rs`, so adding one is a matter of a new button and state.
+
+use std::path::PathBuf;
+
+use crate::buttons::{add_button_list, spawn_buttons, MenuButton, NavigateButtons};
+use crate::challenge::{self, Challenge};
+use crate::controls::{ControlScheme, KeySet};
+use crate::game::{AsteroidPhysics, Difficulty, GameMode, GameSeed, Roster, GAME_MODES};
+use crate::high_scores::HighScores;
+use crate::leaderboard::{Leaderboard, SubmitReply};
+use crate::replay::{self, Replay};
+use crate::replay_viewer::WatchReplay;
+use crate::settings::Settings;
+use crate::{despawn_screen, GameState};
+use bevy::app::AppExit;
 use bevy::prelude::*;
 
+// Pages with nothing to do but read them and go back
+const PAGES: [GameState; 2] = [GameState::Controls, GameState::Credits];
+
+// Co-op games have one player on each side of the keyboard at most
+const MAX_COOP_PLAYERS: usize = 2;
+
+// Room for a seed code with its dashes
+const MAX_CODE_LENGTH: usize = 11;
+
+// Only the newest replays are listed
+const LISTED_REPLAYS: usize = 8;
+
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(GameState::Menu).with_system(menu_setup))
-            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(start_playing))
+        add_button_list::<MainMenuAction>(app, GameState::Menu);
+        add_button_list::<ModeAction>(app, GameState::Modes);
+        add_button_list::<HighScoresAction>(app, GameState::HighScores);
+        add_button_list::<ReplaysAction>(app, GameState::Replays);
+
+        app.insert_resource(CoopPlayers(1))
+            .insert_resource(ShownTable {
+                mode: GameMode::default(),
+                online: false,
+            })
+            .init_resource::<CodeEntry>()
+            .init_resource::<ListedReplays>()
+            .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(menu_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(main_menu_action.after(NavigateButtons))
+                    .with_system(toggle_physics),
+            )
             .add_system_set(
                 SystemSet::on_exit(GameState::Menu).with_system(despawn_screen::<MainMenuScreen>),
-            );
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Modes).with_system(modes_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Modes)
+                    .with_system(mode_action.label("page_action").after(NavigateButtons))
+                    .with_system(players_label.after("page_action"))
+                    .with_system(go_back.after("page_action")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Modes).with_system(despawn_screen::<PageScreen>),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::HighScores).with_system(high_scores_setup),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::HighScores)
+                    .with_system(
+                        high_scores_action
+                            .label("page_action")
+                            .after(NavigateButtons),
+                    )
+                    .with_system(online_scores_text.after("page_action"))
+                    .with_system(go_back.after("page_action")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::HighScores).with_system(despawn_screen::<PageScreen>),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Replays).with_system(replays_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Replays)
+                    .with_system(replays_action.label("page_action").after(NavigateButtons))
+                    .with_system(go_back.after("page_action")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Replays).with_system(despawn_screen::<PageScreen>),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::EnterCode).with_system(enter_code_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::EnterCode)
+                    .with_system(type_code.label("type_code"))
+                    .with_system(play_code.label("page_action").after("type_code"))
+                    .with_system(code_text.after("page_action"))
+                    .with_system(go_back.after("page_action")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::EnterCode).with_system(despawn_screen::<PageScreen>),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Controls).with_system(controls_setup))
+            .add_system_set(SystemSet::on_enter(GameState::Credits).with_system(credits_setup));
+
+        for page in PAGES {
+            add_button_list::<Back>(app, page.clone());
+
+            app.add_system_set(
+                SystemSet::on_update(page.clone())
+                    .with_system(back_action.label("page_action").after(NavigateButtons))
+                    .with_system(go_back.after("page_action")),
+            )
+            .add_system_set(SystemSet::on_exit(page).with_system(despawn_screen::<PageScreen>));
+        }
     }
 }
 
-fn start_playing(mut game_state: ResMut<State<GameState>>, keys: Res<Input<KeyCode>>) {
-    if keys.just_released(KeyCode::J) {
-        game_state.set(GameState::Game).unwrap();
+#[derive(Clone, Copy)]
+enum MainMenuAction {
+    Play,
+    Modes,
+    HighScores,
+    Replays,
+    Settings,
+    Controls,
+    Credits,
+    Quit,
+}
+
+fn main_menu_action(
+    mut actions: EventReader<MainMenuAction>,
+    mut game_state: ResMut<State<GameState>>,
+    mut seed: ResMut<GameSeed>,
+    mut difficulty: ResMut<Difficulty>,
+    settings: Res<Settings>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let next = match actions.iter().last() {
+        // Plays the mode last played, by whoever played it, which is a
+        // classic game for one to begin with
+        Some(MainMenuAction::Play) => {
+            *seed = GameSeed::random();
+            // A seed code may have picked a different one last time
+            *difficulty = settings.difficulty;
+            GameState::Game
+        }
+        Some(MainMenuAction::Modes) => GameState::Modes,
+        Some(MainMenuAction::HighScores) => GameState::HighScores,
+        Some(MainMenuAction::Replays) => GameState::Replays,
+        Some(MainMenuAction::Settings) => GameState::Settings,
+        Some(MainMenuAction::Controls) => GameState::Controls,
+        Some(MainMenuAction::Credits) => GameState::Credits,
+        Some(MainMenuAction::Quit) => {
+            app_exit_events.send(AppExit);
+            return;
+        }
+        None => return,
+    };
+
+    game_state.set(next).unwrap();
+}
+
+#[derive(Clone, Copy)]
+enum ModeAction {
+    Play(GameMode),
+    Daily,
+    EnterCode,
+    Versus,
+    // Changes how many play together in the other modes
+    Players,
+    Back,
+}
+
+// How many play the next co-op game
+struct CoopPlayers(usize);
+
+#[allow(clippy::too_many_arguments)]
+fn mode_action(
+    mut actions: EventReader<ModeAction>,
+    mut game_state: ResMut<State<GameState>>,
+    mut roster: ResMut<Roster>,
+    mut mode: ResMut<GameMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut seed: ResMut<GameSeed>,
+    mut coop_players: ResMut<CoopPlayers>,
+    settings: Res<Settings>,
+    gamepads: Res<Gamepads>,
+) {
+    let challenge = match actions.iter().last() {
+        Some(ModeAction::Play(picked)) => Challenge {
+            seed: GameSeed::random().0,
+            mode: *picked,
+            difficulty: settings.difficulty,
+        },
+        Some(ModeAction::Daily) => Challenge::daily(challenge::today()),
+        Some(ModeAction::Versus) => {
+            roster.players = gamepads.iter().count().clamp(2, 4);
+            roster.versus = true;
+            *seed = GameSeed::random();
+            *difficulty = settings.difficulty;
+            game_state.set(GameState::Game).unwrap();
+            return;
+        }
+        Some(ModeAction::Players) => {
+            coop_players.0 = coop_players.0 % MAX_COOP_PLAYERS + 1;
+            return;
+        }
+        Some(ModeAction::EnterCode) => {
+            game_state.set(GameState::EnterCode).unwrap();
+            return;
+        }
+        Some(ModeAction::Back) => {
+            game_state.set(GameState::Menu).unwrap();
+            return;
+        }
+        None => return,
+    };
+
+    roster.players = coop_players.0;
+    roster.versus = false;
+    play_challenge(challenge, &mut mode, &mut difficulty, &mut seed);
+    game_state.set(GameState::Game).unwrap();
+}
+
+// Sets the next game up to play out like the challenge
+fn play_challenge(
+    challenge: Challenge,
+    mode: &mut GameMode,
+    difficulty: &mut Difficulty,
+    seed: &mut GameSeed,
+) {
+    *mode = challenge.mode;
+    *difficulty = challenge.difficulty;
+    seed.0 = challenge.seed;
+}
+
+fn players_label(
+    coop_players: Res<CoopPlayers>,
+    buttons: Query<(&MenuButton<ModeAction>, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, children) in buttons.iter() {
+        if !matches!(button.action(), ModeAction::Players) {
+            continue;
+        }
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = format!("PLAYERS: {}", coop_players.0);
+            }
+        }
     }
 }
 
+#[derive(Clone, Copy)]
+enum HighScoresAction {
+    NextMode,
+    // Switches between this machine's scores and the leaderboard server's
+    Online,
+    Back,
+}
+
+// The high scores on show
+struct ShownTable {
+    mode: GameMode,
+    // The leaderboard server's, rather than this machine's
+    online: bool,
+}
+
+// Filled in with the leaderboard server's table by `online_scores_text`
+#[derive(Component)]
+struct OnlineScores;
+
+#[allow(clippy::too_many_arguments)]
+fn high_scores_action(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    high_scores: Res<HighScores>,
+    settings: Res<Settings>,
+    mut actions: EventReader<HighScoresAction>,
+    mut game_state: ResMut<State<GameState>>,
+    mut shown: ResMut<ShownTable>,
+    page_query: Query<Entity, With<PageScreen>>,
+) {
+    match actions.iter().last() {
+        Some(HighScoresAction::NextMode) => {
+            let index = GAME_MODES.iter().position(|mode| *mode == shown.mode);
+            shown.mode = GAME_MODES[(index.unwrap_or(0) + 1) % GAME_MODES.len()];
+        }
+        Some(HighScoresAction::Online) => shown.online = !shown.online,
+        Some(HighScoresAction::Back) => {
+            game_state.set(GameState::Menu).unwrap();
+            return;
+        }
+        None => return,
+    }
+
+    // The page is built again around the next table
+    for entity in page_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_high_scores(
+        &mut commands,
+        &asset_server,
+        &high_scores,
+        &settings,
+        &shown,
+    );
+}
+
+fn online_scores_text(
+    settings: Res<Settings>,
+    shown: Res<ShownTable>,
+    mut leaderboard: NonSendMut<Leaderboard>,
+    mut query: Query<&mut Text, With<OnlineScores>>,
+) {
+    let url = match &settings.leaderboard_url {
+        Some(url) if shown.online => url,
+        _ => return,
+    };
+    leaderboard.fetch(url, shown.mode);
+
+    let mut lines = Vec::new();
+    if let Some((mode, submission)) = &leaderboard.submission {
+        lines.push(match submission.answer() {
+            None => format!("SENDING YOUR LAST {} GAME...", mode.name()),
+            Some(Ok(SubmitReply {
+                rank: Some(rank), ..
+            })) => format!("YOUR LAST {} GAME PLACED #{}", mode.name(), rank),
+            Some(Ok(reply)) => format!(
+                "YOUR LAST {} GAME WAS TURNED DOWN: {}",
+                mode.name(),
+                reply.errors.join(", ").to_uppercase()
+            ),
+            Some(Err(_)) => format!("YOUR LAST {} GAME COULDN'T BE SENT", mode.name()),
+        });
+        lines.push(String::new());
+    }
+
+    match leaderboard
+        .table
+        .as_ref()
+        .and_then(|(_, table)| table.answer())
+    {
+        None => lines.push("LOADING...".into()),
+        Some(Err(_)) => lines.push(format!("CAN'T REACH {}", url)),
+        Some(Ok(entries)) if entries.is_empty() => lines.push("NO SCORES YET".into()),
+        Some(Ok(entries)) => lines.extend(entries.iter().enumerate().map(|(place, entry)| {
+            score_line(
+                place,
+                Some(&entry.name),
+                entry.points,
+                entry.wave,
+                entry.seconds,
+                entry.players,
+            )
+        })),
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ReplaysAction {
+    // By position in `ListedReplays`
+    Watch(usize),
+    Back,
+}
+
+// The replay files on the Replays page, in the order they're listed
+#[derive(Default)]
+struct ListedReplays(Vec<PathBuf>);
+
+fn replays_action(
+    mut actions: EventReader<ReplaysAction>,
+    mut game_state: ResMut<State<GameState>>,
+    listed: Res<ListedReplays>,
+    mut watch: ResMut<WatchReplay>,
+) {
+    match actions.iter().last() {
+        Some(ReplaysAction::Watch(index)) => {
+            watch.0 = listed.0.get(*index).cloned();
+            game_state.set(GameState::Playback).unwrap();
+        }
+        Some(ReplaysAction::Back) => game_state.set(GameState::Menu).unwrap(),
+        None => {}
+    }
+}
+
+// The seed code being typed in
+#[derive(Default)]
+struct CodeEntry {
+    code: String,
+    // Set when the code typed so far was tried and isn't one
+    invalid: bool,
+}
+
+#[derive(Component)]
+struct CodeText;
+
+#[derive(Component)]
+struct CodeHint;
+
+fn type_code(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut entry: ResMut<CodeEntry>,
+) {
+    for character in characters.iter() {
+        let character = character.char.to_ascii_uppercase();
+
+        if (character.is_ascii_alphanumeric() || character == '-')
+            && entry.code.len() < MAX_CODE_LENGTH
+        {
+            entry.code.push(character);
+            entry.invalid = false;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        entry.code.pop();
+        entry.invalid = false;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_code(
+    keys: Res<Input<KeyCode>>,
+    mut entry: ResMut<CodeEntry>,
+    mut game_state: ResMut<State<GameState>>,
+    mut roster: ResMut<Roster>,
+    mut mode: ResMut<GameMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut seed: ResMut<GameSeed>,
+    coop_players: Res<CoopPlayers>,
+) {
+    // Nothing typed yet may mean the enter that opened the page
+    if !keys.just_released(KeyCode::Return) || entry.code.is_empty() {
+        return;
+    }
+
+    match Challenge::from_code(&entry.code) {
+        Some(challenge) => {
+            roster.players = coop_players.0;
+            roster.versus = false;
+            play_challenge(challenge, &mut mode, &mut difficulty, &mut seed);
+            game_state.set(GameState::Game).unwrap();
+        }
+        None => entry.invalid = true,
+    }
+}
+
+fn code_text(
+    entry: Res<CodeEntry>,
+    mut code_query: Query<&mut Text, (With<CodeText>, Without<CodeHint>)>,
+    mut hint_query: Query<&mut Text, With<CodeHint>>,
+) {
+    for mut text in code_query.iter_mut() {
+        text.sections[0].value = format!("{}_", entry.code);
+    }
+
+    for mut text in hint_query.iter_mut() {
+        text.sections[0].value = if entry.invalid {
+            "THAT'S NOT A SEED CODE".into()
+        } else {
+            "[ENTER] PLAY   [ESC] BACK".into()
+        };
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Back;
+
+fn back_action(mut actions: EventReader<Back>, mut game_state: ResMut<State<GameState>>) {
+    if actions.iter().last().is_some() {
+        game_state.set(GameState::Menu).unwrap();
+    }
+}
+
+// Escape, or B on a gamepad, leaves any page for the title screen
+fn go_back(
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let back = keys.just_released(KeyCode::Escape)
+        || gamepad_buttons
+            .get_just_released()
+            .any(|button| button.1 == GamepadButtonType::East);
+
+    // A button may already have picked somewhere to go this frame
+    if back {
+        let _ = game_state.set(GameState::Menu);
+    }
+}
+
+fn toggle_physics(
+    keys: Res<Input<KeyCode>>,
+    mut physics: ResMut<AsteroidPhysics>,
+    mut query: Query<&mut Text, With<PhysicsText>>,
+) {
+    if keys.just_released(KeyCode::P) {
+        physics.enabled = !physics.enabled;
+
+        for mut text in query.iter_mut() {
+            text.sections[0].value = physics_label(&physics);
+        }
+    }
+}
+
+fn physics_label(physics: &AsteroidPhysics) -> String {
+    format!(
+        "[P] ASTEROID PHYSICS: {}",
+        if physics.enabled { "ON" } else { "OFF" }
+    )
+}
+
 #[derive(Component)]
 struct MainMenuScreen;
 
-fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+#[derive(Component)]
+struct PhysicsText;
+
+fn menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    physics: Res<AsteroidPhysics>,
+) {
     let font = asset_server.load("Orbitron.ttf");
 
     commands
@@ -33,7 +538,8 @@ fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 align_items: AlignItems::Center,
                 ..Default::default()
             },
-            color: Color::BLACK.into(),
+            // Clear, so the attract mode demo shows through
+            color: Color::NONE.into(),
             ..Default::default()
         })
         .insert(MainMenuScreen)
@@ -55,21 +561,398 @@ fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..Default::default()
             });
 
+            spawn_buttons(
+                parent,
+                &font,
+                &[
+                    ("PLAY", MainMenuAction::Play),
+                    ("MODES", MainMenuAction::Modes),
+                    ("HIGH SCORES", MainMenuAction::HighScores),
+                    ("REPLAYS", MainMenuAction::Replays),
+                    ("SETTINGS", MainMenuAction::Settings),
+                    ("CONTROLS", MainMenuAction::Controls),
+                    ("CREDITS", MainMenuAction::Credits),
+                    ("QUIT", MainMenuAction::Quit),
+                ],
+            );
+
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        physics_label(&physics),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: Color::GRAY,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PhysicsText);
+        });
+}
+
+// Marks everything on the pages below the title screen
+#[derive(Component)]
+struct PageScreen;
+
+// A page with a title, some lines of text and then buttons
+fn spawn_page(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    title: &str,
+    lines: &[String],
+    buttons: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(PageScreen)
+        .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 style: Style {
-                    margin: Rect::all(Val::Px(50.0)),
+                    margin: Rect::all(Val::Px(30.0)),
                     ..Default::default()
                 },
                 text: Text::with_section(
-                    "PRESS [J] PLAY",
+                    title,
                     TextStyle {
                         font: font.clone(),
-                        font_size: 40.0,
+                        font_size: 60.0,
                         color: Color::WHITE,
                     },
                     Default::default(),
                 ),
                 ..Default::default()
             });
+
+            for line in lines {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(6.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        line.as_str(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: Color::GRAY,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(30.0)),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(buttons);
         });
 }
+
+fn modes_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("Orbitron.ttf");
+
+    spawn_page(&mut commands, &font, "Modes", &[], |parent| {
+        spawn_buttons(
+            parent,
+            &font,
+            &[
+                ("CLASSIC", ModeAction::Play(GameMode::Classic)),
+                ("TIME ATTACK", ModeAction::Play(GameMode::TimeAttack)),
+                ("SURVIVAL", ModeAction::Play(GameMode::Survival)),
+                ("ZEN", ModeAction::Play(GameMode::Zen)),
+                ("DAILY CHALLENGE", ModeAction::Daily),
+                ("ENTER CODE", ModeAction::EnterCode),
+                ("VERSUS", ModeAction::Versus),
+                // Filled in by `players_label`
+                ("", ModeAction::Players),
+                ("BACK", ModeAction::Back),
+            ],
+        )
+    });
+}
+
+fn enter_code_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut entry: ResMut<CodeEntry>,
+) {
+    let font = asset_server.load("Orbitron.ttf");
+    *entry = CodeEntry::default();
+
+    let lines = ["TYPE THE CODE FROM THE END OF SOMEONE'S RUN".into()];
+    spawn_page(&mut commands, &font, "Enter Code", &lines, |parent| {
+        // Both filled in by `code_text`
+        parent
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(20.0)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 48.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(CodeText);
+
+        parent
+            .spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 24.0,
+                        color: Color::GRAY,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(CodeHint);
+    });
+}
+
+fn back_page(commands: &mut Commands, asset_server: &AssetServer, title: &str, lines: &[String]) {
+    let font = asset_server.load("Orbitron.ttf");
+
+    spawn_page(commands, &font, title, lines, |parent| {
+        spawn_buttons(parent, &font, &[("BACK", Back)])
+    });
+}
+
+fn high_scores_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    high_scores: Res<HighScores>,
+    settings: Res<Settings>,
+    shown: Res<ShownTable>,
+) {
+    spawn_high_scores(
+        &mut commands,
+        &asset_server,
+        &high_scores,
+        &settings,
+        &shown,
+    );
+}
+
+fn spawn_high_scores(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    high_scores: &HighScores,
+    settings: &Settings,
+    shown: &ShownTable,
+) {
+    let font = asset_server.load("Orbitron.ttf");
+    let has_leaderboard = settings.leaderboard_url.is_some();
+    let online = shown.online && has_leaderboard;
+
+    let mut lines = Vec::new();
+    if online {
+        lines.push(format!("- {} ONLINE -", shown.mode.name()));
+    } else {
+        let table = high_scores.table(shown.mode);
+
+        lines.push(format!("- {} -", shown.mode.name()));
+        if table.is_empty() {
+            lines.push("NO SCORES YET".into());
+        }
+        lines.extend(table.iter().enumerate().map(|(place, score)| {
+            score_line(
+                place,
+                None,
+                score.points,
+                score.wave,
+                score.seconds,
+                score.players,
+            )
+        }));
+    }
+
+    let mut buttons = vec![("NEXT MODE", HighScoresAction::NextMode)];
+    if has_leaderboard {
+        let label = if online {
+            "LOCAL SCORES"
+        } else {
+            "ONLINE SCORES"
+        };
+        buttons.push((label, HighScoresAction::Online));
+    }
+    buttons.push(("BACK", HighScoresAction::Back));
+
+    spawn_page(commands, &font, "High Scores", &lines, |parent| {
+        if online {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: Color::GRAY,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(OnlineScores);
+        }
+
+        spawn_buttons(parent, &font, &buttons)
+    });
+}
+
+// A line of a high score table, with the player's name if it's known
+fn score_line(
+    place: usize,
+    name: Option<&str>,
+    points: i32,
+    wave: u32,
+    seconds: f32,
+    players: usize,
+) -> String {
+    let seconds = seconds as u32;
+    format!(
+        "{}.  {}{}  WAVE {}  {}:{:02}{}",
+        place + 1,
+        name.map_or(String::new(), |name| format!("{}  ", name.to_uppercase())),
+        points,
+        wave.max(1),
+        seconds / 60,
+        seconds % 60,
+        if players > 1 { "  CO-OP" } else { "" }
+    )
+}
+
+fn replays_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut listed: ResMut<ListedReplays>,
+) {
+    let font = asset_server.load("Orbitron.ttf");
+
+    // Files that can't be read, e.g. from an older version, are left out
+    let replays: Vec<(PathBuf, Replay)> = replay::list()
+        .into_iter()
+        .filter_map(|path| Replay::load(&path).ok().map(|replay| (path, replay)))
+        .take(LISTED_REPLAYS)
+        .collect();
+
+    let labels: Vec<String> = replays
+        .iter()
+        .map(|(_, replay)| replay_label(replay))
+        .collect();
+    let mut buttons: Vec<(&str, ReplaysAction)> = labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| (label.as_str(), ReplaysAction::Watch(index)))
+        .collect();
+    buttons.push(("BACK", ReplaysAction::Back));
+
+    let lines = if replays.is_empty() {
+        vec!["NO REPLAYS YET, EVERY GAME YOU PLAY IS SAVED HERE".into()]
+    } else {
+        Vec::new()
+    };
+    spawn_page(&mut commands, &font, "Replays", &lines, |parent| {
+        spawn_buttons(parent, &font, &buttons)
+    });
+
+    listed.0 = replays.into_iter().map(|(path, _)| path).collect();
+}
+
+// When the game was played, in UTC, what it was and how it went
+fn replay_label(replay: &Replay) -> String {
+    let minutes = replay.recorded_at / 60;
+    let mode = if replay.roster.versus {
+        "VERSUS"
+    } else {
+        replay.mode.name()
+    };
+
+    format!(
+        "{} {:02}:{:02}  {}  {}",
+        challenge::date_text(minutes / (24 * 60)),
+        minutes / 60 % 24,
+        minutes % 60,
+        mode,
+        replay.points()
+    )
+}
+
+fn key_set_text(keys: &KeySet) -> String {
+    format!(
+        "{:?} {:?} {:?} {:?} FLY   {:?} FIRE   {:?} HYPERSPACE   {:?} WEAPON",
+        keys.thrust,
+        keys.reverse,
+        keys.left,
+        keys.right,
+        keys.fire,
+        keys.hyperspace,
+        keys.switch_weapon
+    )
+    .to_uppercase()
+}
+
+fn controls_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scheme: Res<ControlScheme>,
+) {
+    let mut lines: Vec<String> = (0..2)
+        .filter_map(|player| {
+            let keys = scheme.key_set(player)?;
+            Some(format!("PLAYER {}: {}", player + 1, key_set_text(&keys)))
+        })
+        .collect();
+    lines.push("GAMEPADS: STICK OR D-PAD FLY   A FIRE   B HYPERSPACE   Y WEAPON".into());
+    lines.push("SWAP THE KEYBOARD SIDES IN SETTINGS".into());
+
+    back_page(&mut commands, &asset_server, "Controls", &lines);
+}
+
+fn credits_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    back_page(
+        &mut commands,
+        &asset_server,
+        "Credits",
+        &[
+            "MADE WITH BEVY".into(),
+            "ORBITRON TYPEFACE BY MATT MCINERNEY".into(),
+        ],
+    );
+}
@@ -0,0 +1,504 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use super::{receive, send, ClientMessage, ServerMessage, Snapshot, TIMEOUT_SECONDS};
+use crate::controls::{read_controls, ControlScheme, Controls, ShipInput};
+use crate::game::{
+    scoreboard_update, spawn_hud, step_ship, GameAssets, GameMode, Hud, MatchTimer, Player, Roster,
+    Scoreboard, VersusRules, PLAYER_COLORS,
+};
+use crate::power_ups::power_up_sprite;
+use crate::{despawn_screen, GameState};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+// Inputs kept for replaying on top of snapshots, about two seconds' worth. A
+// server that's fallen this far behind has most likely gone, so older inputs
+// are dropped rather than piling up until the timeout notices.
+const MAX_PENDING_INPUTS: usize = 120;
+
+// Plays a game hosted on a server. The client draws whatever the server's
+// snapshots contain, except for its own ship, which it moves straight away
+// from local input and corrects whenever a snapshot arrives.
+pub struct NetClientPlugin {
+    pub server: SocketAddr,
+}
+
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind("0.0.0.0:0").expect("failed to bind client socket");
+        socket
+            .set_nonblocking(true)
+            .expect("failed to make client socket non-blocking");
+
+        app.insert_resource(Connection {
+            socket,
+            server: self.server,
+            player: None,
+            last_frame: 0,
+            last_heard: 0.0,
+            sequence: 0,
+            pending: VecDeque::new(),
+            join_timer: Timer::from_seconds(0.5, true),
+        })
+        .init_resource::<Roster>()
+        .init_resource::<VersusRules>()
+        .init_resource::<GameAssets>()
+        .init_resource::<ControlScheme>()
+        .init_resource::<GameMode>()
+        .insert_resource(Scoreboard {
+            players: Vec::new(),
+        })
+        .insert_resource(MatchTimer(Timer::from_seconds(0.0, false)))
+        .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(connecting_setup))
+        .add_system_set(
+            SystemSet::on_update(GameState::Menu)
+                .with_system(join)
+                .with_system(wait_for_welcome),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Menu).with_system(despawn_screen::<ConnectingScreen>),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Game)
+                .with_system(apply_snapshots.label(NetClientSystem::Receive))
+                .with_system(
+                    read_controls
+                        .label(NetClientSystem::Input)
+                        .after(NetClientSystem::Receive),
+                )
+                .with_system(
+                    send_input
+                        .label(NetClientSystem::Send)
+                        .after(NetClientSystem::Input),
+                )
+                .with_system(predict_ship.after(NetClientSystem::Send))
+                .with_system(scoreboard_update.after(NetClientSystem::Receive)),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Game)
+                .with_system(despawn_screen::<NetEntity>)
+                .with_system(despawn_screen::<Hud>),
+        );
+    }
+}
+
+#[derive(SystemLabel, Clone, Copy, Hash, Debug, PartialEq, Eq)]
+enum NetClientSystem {
+    Receive,
+    Input,
+    Send,
+}
+
+struct Connection {
+    socket: UdpSocket,
+    server: SocketAddr,
+    // Our seat, once the server has let us in
+    player: Option<usize>,
+    // The newest snapshot applied so far
+    last_frame: u64,
+    // When the server was last heard from, once the game has started
+    last_heard: f64,
+    sequence: u32,
+    // Inputs the server hasn't applied yet, replayed on top of each snapshot
+    pending: VecDeque<PendingInput>,
+    join_timer: Timer,
+}
+
+struct PendingInput {
+    sequence: u32,
+    input: ShipInput,
+    dt: f32,
+}
+
+// Anything drawn from the server's snapshots, removed when the game ends
+#[derive(Component)]
+struct NetEntity;
+
+#[derive(Component)]
+struct RemoteAsteroid(u64);
+
+#[derive(Component)]
+struct RemoteBullet(u64);
+
+#[derive(Component)]
+struct RemotePowerUp(u64);
+
+#[derive(Component)]
+struct ConnectingScreen;
+
+fn connecting_setup(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    connection: Res<Connection>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    // Back from the results screen: the game we came for is over
+    if connection.player.is_some() {
+        send(&connection.socket, connection.server, &ClientMessage::Leave);
+        app_exit_events.send(AppExit);
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                format!("CONNECTING TO {}", connection.server),
+                TextStyle {
+                    font: assets.font.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ConnectingScreen);
+}
+
+fn join(time: Res<Time>, mut connection: ResMut<Connection>) {
+    if connection.join_timer.tick(time.delta()).just_finished() {
+        send(&connection.socket, connection.server, &ClientMessage::Join);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn wait_for_welcome(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    mut connection: ResMut<Connection>,
+    mut roster: ResMut<Roster>,
+    mut rules: ResMut<VersusRules>,
+    mut match_timer: ResMut<MatchTimer>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    for (_, message) in receive::<ServerMessage>(&connection.socket) {
+        match message {
+            ServerMessage::Welcome {
+                player,
+                roster: server_roster,
+                time_limit,
+            } => {
+                info!("joined as player {}", player + 1);
+                connection.player = Some(player);
+                connection.last_heard = time.seconds_since_startup();
+                *roster = server_roster;
+                rules.time_limit = time_limit;
+                match_timer.0 = Timer::from_seconds(time_limit.unwrap_or(0.0), false);
+
+                spawn_hud(
+                    &mut commands,
+                    &assets.font,
+                    &roster,
+                    rules.time_limit.filter(|_| roster.versus),
+                );
+                game_state.set(GameState::Game).unwrap();
+                return;
+            }
+            ServerMessage::Full => warn!("server is full, still trying"),
+            ServerMessage::Snapshot(_) => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_snapshots(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    mut connection: ResMut<Connection>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut match_timer: ResMut<MatchTimer>,
+    mut game_state: ResMut<State<GameState>>,
+    scheme: Res<ControlScheme>,
+    mut ship_query: Query<(Entity, &mut Player, &mut Transform)>,
+    asteroid_query: Query<(Entity, &RemoteAsteroid)>,
+    bullet_query: Query<(Entity, &RemoteBullet)>,
+    power_up_query: Query<(Entity, &RemotePowerUp)>,
+) {
+    let now = time.seconds_since_startup();
+    let messages = receive::<ServerMessage>(&connection.socket);
+    if !messages.is_empty() {
+        connection.last_heard = now;
+    } else if now - connection.last_heard > TIMEOUT_SECONDS {
+        warn!("lost contact with {}", connection.server);
+        game_state.set(GameState::GameOver).unwrap();
+        return;
+    }
+
+    // Only the newest snapshot matters, anything older is already out of date
+    let snapshot = messages
+        .into_iter()
+        .filter_map(|(_, message)| match message {
+            ServerMessage::Snapshot(snapshot) => Some(snapshot),
+            _ => None,
+        })
+        .max_by_key(|snapshot| snapshot.frame);
+
+    let snapshot = match snapshot {
+        Some(snapshot) if snapshot.frame > connection.last_frame => snapshot,
+        _ => return,
+    };
+    connection.last_frame = snapshot.frame;
+
+    let local_player = connection.player;
+    if let Some(acked) = local_player.and_then(|player| snapshot.acked.get(player)) {
+        let acked = *acked;
+        connection
+            .pending
+            .retain(|pending| pending.sequence > acked);
+    }
+
+    apply_ships(
+        &mut commands,
+        &assets,
+        &snapshot,
+        local_player,
+        *scheme,
+        &connection.pending,
+        &mut ship_query,
+    );
+    apply_asteroids(&mut commands, &assets, &snapshot, &asteroid_query);
+    apply_bullets(&mut commands, &snapshot, &bullet_query);
+    apply_power_ups(&mut commands, &snapshot, &power_up_query);
+
+    *scoreboard = snapshot.scoreboard;
+
+    let duration = match_timer.0.duration();
+    let left = Duration::from_secs_f32(snapshot.match_time_left.max(0.0)).min(duration);
+    match_timer.0.set_elapsed(duration - left);
+
+    if snapshot.over {
+        game_state.set(GameState::GameOver).unwrap();
+    }
+}
+
+fn apply_ships(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    snapshot: &Snapshot,
+    local_player: Option<usize>,
+    scheme: ControlScheme,
+    pending: &VecDeque<PendingInput>,
+    ship_query: &mut Query<(Entity, &mut Player, &mut Transform)>,
+) {
+    for (entity, player, _) in ship_query.iter_mut() {
+        if snapshot.ships.iter().all(|ship| ship.player != player.id) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for ship in snapshot.ships.iter() {
+        let mut transform = Transform {
+            translation: Vec2::from(ship.translation).extend(0.0),
+            rotation: Quat::from_rotation_z(ship.angle),
+            scale: Vec3::new(1.0, 1.0, 0.0),
+        };
+        let mut velocity = Vec2::from(ship.velocity);
+
+        // Re-run the inputs the server hasn't seen yet, so our own ship stays
+        // where we've been steering it
+        if Some(ship.player) == local_player {
+            for input in pending.iter() {
+                step_ship(&mut velocity, &mut transform, &input.input, input.dt);
+            }
+        }
+
+        let existing = ship_query
+            .iter_mut()
+            .find(|(_, player, _)| player.id == ship.player);
+
+        match existing {
+            Some((_, mut player, mut ship_transform)) => {
+                player.velocity = velocity;
+                *ship_transform = transform;
+            }
+            None => {
+                let mut ship_commands = commands.spawn_bundle(SpriteBundle {
+                    transform,
+                    sprite: Sprite {
+                        color: PLAYER_COLORS[ship.player % PLAYER_COLORS.len()],
+                        ..Default::default()
+                    },
+                    texture: assets.player.clone(),
+                    ..Default::default()
+                });
+                ship_commands
+                    .insert(Player {
+                        id: ship.player,
+                        velocity,
+                    })
+                    .insert(NetEntity);
+
+                if Some(ship.player) == local_player {
+                    ship_commands
+                        .insert(Controls::for_player(0, scheme))
+                        .insert(ShipInput::default());
+                }
+            }
+        }
+    }
+}
+
+fn apply_asteroids(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    snapshot: &Snapshot,
+    asteroid_query: &Query<(Entity, &RemoteAsteroid)>,
+) {
+    let mut existing: HashMap<u64, Entity> = asteroid_query
+        .iter()
+        .map(|(entity, asteroid)| (asteroid.0, entity))
+        .collect();
+
+    for asteroid in snapshot.asteroids.iter() {
+        let transform = Transform {
+            translation: Vec2::from(asteroid.translation).extend(0.0),
+            rotation: Quat::from_rotation_z(asteroid.angle),
+            scale: Vec3::new(asteroid.scale, asteroid.scale, asteroid.scale),
+        };
+
+        match existing.remove(&asteroid.id) {
+            Some(entity) => {
+                commands.entity(entity).insert(transform);
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        transform,
+                        texture: assets.asteroid.clone(),
+                        ..Default::default()
+                    })
+                    .insert(RemoteAsteroid(asteroid.id))
+                    .insert(NetEntity);
+            }
+        }
+    }
+
+    // Whatever is left has been destroyed on the server
+    for entity in existing.into_values() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn apply_bullets(
+    commands: &mut Commands,
+    snapshot: &Snapshot,
+    bullet_query: &Query<(Entity, &RemoteBullet)>,
+) {
+    let mut existing: HashMap<u64, Entity> = bullet_query
+        .iter()
+        .map(|(entity, bullet)| (bullet.0, entity))
+        .collect();
+
+    for bullet in snapshot.bullets.iter() {
+        let transform = Transform {
+            translation: Vec2::from(bullet.translation).extend(0.0),
+            rotation: Quat::from_rotation_z(bullet.angle),
+            scale: bullet.weapon.projectile_size().extend(0.0),
+        };
+
+        match existing.remove(&bullet.id) {
+            Some(entity) => {
+                commands.entity(entity).insert(transform);
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        transform,
+                        sprite: Sprite {
+                            color: PLAYER_COLORS[bullet.owner % PLAYER_COLORS.len()],
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(RemoteBullet(bullet.id))
+                    .insert(NetEntity);
+            }
+        }
+    }
+
+    for entity in existing.into_values() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn apply_power_ups(
+    commands: &mut Commands,
+    snapshot: &Snapshot,
+    power_up_query: &Query<(Entity, &RemotePowerUp)>,
+) {
+    let mut existing: HashMap<u64, Entity> = power_up_query
+        .iter()
+        .map(|(entity, power_up)| (power_up.0, entity))
+        .collect();
+
+    for power_up in snapshot.power_ups.iter() {
+        let sprite = power_up_sprite(power_up.kind, Vec2::from(power_up.translation).extend(0.0));
+
+        match existing.remove(&power_up.id) {
+            Some(entity) => {
+                commands.entity(entity).insert(sprite.transform);
+            }
+            None => {
+                commands
+                    .spawn_bundle(sprite)
+                    .insert(RemotePowerUp(power_up.id))
+                    .insert(NetEntity);
+            }
+        }
+    }
+
+    for entity in existing.into_values() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn send_input(
+    time: Res<Time>,
+    mut connection: ResMut<Connection>,
+    query: Query<&ShipInput, With<Controls>>,
+) {
+    let input = match query.iter().next() {
+        Some(input) => *input,
+        None => return,
+    };
+
+    connection.sequence += 1;
+    let sequence = connection.sequence;
+
+    send(
+        &connection.socket,
+        connection.server,
+        &ClientMessage::Input { sequence, input },
+    );
+    connection.pending.push_back(PendingInput {
+        sequence,
+        input,
+        dt: time.delta_seconds(),
+    });
+    if connection.pending.len() > MAX_PENDING_INPUTS {
+        connection.pending.pop_front();
+    }
+}
+
+// Moves our own ship right away instead of waiting a round trip for the server
+fn predict_ship(
+    time: Res<Time>,
+    mut query: Query<(&mut Player, &ShipInput, &mut Transform), With<Controls>>,
+) {
+    for (mut player, input, mut transform) in query.iter_mut() {
+        step_ship(
+            &mut player.velocity,
+            &mut transform,
+            input,
+            time.delta_seconds(),
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// Networked play over UDP. A dedicated server runs the game simulation and
+// streams snapshots of the world to clients, which only send their inputs.
+
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::controls::ShipInput;
+use crate::game::{Roster, Scoreboard};
+use crate::power_ups::PowerUpKind;
+use crate::weapons::WeaponKind;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub mod client;
+pub mod server;
+
+pub const DEFAULT_PORT: u16 = 7777;
+
+// Large enough for any UDP datagram
+const MAX_PACKET_SIZE: usize = 65536;
+
+// Clients, or peers, that go quiet for this long are dropped
+pub(crate) const TIMEOUT_SECONDS: f64 = 5.0;
+
+#[derive(Serialize, Deserialize)]
+pub enum ClientMessage {
+    // Asks for a seat in the next game. Sent repeatedly until welcomed.
+    Join,
+    Input { sequence: u32, input: ShipInput },
+    Leave,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        player: usize,
+        roster: Roster,
+        time_limit: Option<f32>,
+    },
+    // Every seat is taken, or a game is already running
+    Full,
+    Snapshot(Snapshot),
+}
+
+// Everything a client needs to draw one frame of the game
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub frame: u64,
+    pub over: bool,
+    // The last input sequence applied for each player, so clients know which
+    // of their predicted inputs still need replaying
+    pub acked: Vec<u32>,
+    pub ships: Vec<ShipState>,
+    pub asteroids: Vec<AsteroidState>,
+    pub bullets: Vec<BulletState>,
+    pub power_ups: Vec<PowerUpState>,
+    pub scoreboard: Scoreboard,
+    pub match_time_left: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShipState {
+    pub player: usize,
+    pub translation: [f32; 2],
+    pub angle: f32,
+    pub velocity: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AsteroidState {
+    pub id: u64,
+    pub translation: [f32; 2],
+    pub angle: f32,
+    pub scale: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BulletState {
+    pub id: u64,
+    pub translation: [f32; 2],
+    pub angle: f32,
+    pub owner: usize,
+    pub weapon: WeaponKind,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PowerUpState {
+    pub id: u64,
+    pub translation: [f32; 2],
+    pub kind: PowerUpKind,
+}
+
+pub(crate) fn send<T: Serialize>(socket: &UdpSocket, to: SocketAddr, message: &T) {
+    match bincode::serialize(message) {
+        Ok(bytes) => {
+            if let Err(error) = socket.send_to(&bytes, to) {
+                bevy::log::warn!("failed to send to {}: {}", to, error);
+            }
+        }
+        Err(error) => bevy::log::error!("failed to encode message: {}", error),
+    }
+}
+
+// Reads every datagram waiting on a non-blocking socket, skipping any that
+// don't decode
+pub(crate) fn receive<T: DeserializeOwned>(socket: &UdpSocket) -> Vec<(SocketAddr, T)> {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    let mut messages = Vec::new();
+
+    while let Ok((length, from)) = socket.recv_from(&mut buffer) {
+        if let Ok(message) = bincode::deserialize(&buffer[..length]) {
+            messages.push((from, message));
+        }
+    }
+
+    messages
+}
@@ -0,0 +1,266 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use super::{
+    receive, send, AsteroidState, BulletState, ClientMessage, PowerUpState, ServerMessage,
+    ShipState, Snapshot, TIMEOUT_SECONDS,
+};
+use crate::controls::ShipInput;
+use crate::game::{
+    quat_to_angle, Asteroid, Bullet, GameSeed, MatchTimer, Player, Roster, Scoreboard, VersusRules,
+};
+use crate::power_ups::PowerUp;
+use crate::GameState;
+use bevy::prelude::*;
+
+// Runs the authoritative side of a networked game. Players join while the
+// server sits in `GameState::Menu`, and the game starts once every seat in the
+// `Roster` is taken.
+pub struct NetServerPlugin {
+    pub bind: SocketAddr,
+}
+
+impl Plugin for NetServerPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind(self.bind).expect("failed to bind server socket");
+        socket
+            .set_nonblocking(true)
+            .expect("failed to make server socket non-blocking");
+
+        let address = socket
+            .local_addr()
+            .expect("failed to read server socket address");
+        info!("listening on {}", address);
+
+        app.insert_resource(ServerAddress(address))
+            .insert_resource(Server {
+                socket,
+                clients: Vec::new(),
+                frame: 0,
+            })
+            .insert_resource(LobbyTimer(Timer::from_seconds(3.0, false)))
+            .add_system_to_stage(CoreStage::PreUpdate, receive_messages)
+            .add_system_to_stage(CoreStage::Last, send_snapshots)
+            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(start_when_full))
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(reset_lobby_timer))
+            .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(return_to_lobby));
+    }
+}
+
+// Where the server is listening, which is only known once it has bound when
+// `bind` asks for any free port
+pub struct ServerAddress(pub SocketAddr);
+
+struct Server {
+    socket: UdpSocket,
+    clients: Vec<Client>,
+    frame: u64,
+}
+
+struct Client {
+    addr: SocketAddr,
+    player: usize,
+    input: ShipInput,
+    sequence: u32,
+    last_heard: f64,
+}
+
+// How long the final results are sent out before the server takes new players
+struct LobbyTimer(Timer);
+
+fn receive_messages(
+    time: Res<Time>,
+    roster: Res<Roster>,
+    rules: Res<VersusRules>,
+    game_state: Res<State<GameState>>,
+    mut server: ResMut<Server>,
+    mut ship_query: Query<(&Player, &mut ShipInput)>,
+) {
+    let now = time.seconds_since_startup();
+    let in_lobby = *game_state.current() == GameState::Menu;
+
+    for (from, message) in receive::<ClientMessage>(&server.socket) {
+        let known = server.clients.iter().position(|client| client.addr == from);
+
+        match (message, known) {
+            (ClientMessage::Join, None) => {
+                let free_seat = (0..roster.players)
+                    .find(|seat| server.clients.iter().all(|client| client.player != *seat));
+
+                match free_seat {
+                    Some(player) if in_lobby => {
+                        info!("{} joined as player {}", from, player + 1);
+                        server.clients.push(Client {
+                            addr: from,
+                            player,
+                            input: ShipInput::default(),
+                            sequence: 0,
+                            last_heard: now,
+                        });
+                        welcome(&server.socket, from, player, &roster, &rules);
+                    }
+                    _ => send(&server.socket, from, &ServerMessage::Full),
+                }
+            }
+            // The welcome got lost, so send it again
+            (ClientMessage::Join, Some(index)) => {
+                server.clients[index].last_heard = now;
+                let player = server.clients[index].player;
+                welcome(&server.socket, from, player, &roster, &rules);
+            }
+            (ClientMessage::Input { sequence, input }, Some(index)) => {
+                let client = &mut server.clients[index];
+                client.last_heard = now;
+
+                // Datagrams can arrive out of order, so only take newer input
+                if sequence > client.sequence {
+                    client.sequence = sequence;
+                    client.input = input;
+                }
+            }
+            (ClientMessage::Leave, Some(index)) => {
+                info!("player {} left", server.clients[index].player + 1);
+                server.clients.remove(index);
+            }
+            _ => {}
+        }
+    }
+
+    server.clients.retain(|client| {
+        let alive = now - client.last_heard < TIMEOUT_SECONDS;
+        if !alive {
+            info!("player {} timed out", client.player + 1);
+        }
+        alive
+    });
+
+    // Ships without a connected client just drift
+    for (player, mut input) in ship_query.iter_mut() {
+        *input = server
+            .clients
+            .iter()
+            .find(|client| client.player == player.id)
+            .map_or_else(ShipInput::default, |client| client.input);
+    }
+}
+
+fn welcome(
+    socket: &UdpSocket,
+    to: SocketAddr,
+    player: usize,
+    roster: &Roster,
+    rules: &VersusRules,
+) {
+    send(
+        socket,
+        to,
+        &ServerMessage::Welcome {
+            player,
+            roster: roster.clone(),
+            time_limit: rules.time_limit,
+        },
+    );
+}
+
+fn start_when_full(
+    roster: Res<Roster>,
+    server: Res<Server>,
+    mut seed: ResMut<GameSeed>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if server.clients.len() == roster.players {
+        info!("all {} players connected, starting", roster.players);
+        seed.0 = rand::random();
+        game_state.set(GameState::Game).unwrap();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send_snapshots(
+    roster: Res<Roster>,
+    game_state: Res<State<GameState>>,
+    scoreboard: Res<Scoreboard>,
+    match_timer: Res<MatchTimer>,
+    mut server: ResMut<Server>,
+    ship_query: Query<(&Player, &Transform)>,
+    asteroid_query: Query<(Entity, &Transform), With<Asteroid>>,
+    bullet_query: Query<(Entity, &Bullet, &Transform)>,
+    power_up_query: Query<(Entity, &PowerUp, &Transform)>,
+) {
+    if *game_state.current() == GameState::Menu {
+        return;
+    }
+
+    server.frame += 1;
+
+    let mut acked = vec![0; roster.players];
+    for client in server.clients.iter() {
+        acked[client.player] = client.sequence;
+    }
+
+    let snapshot = ServerMessage::Snapshot(Snapshot {
+        frame: server.frame,
+        over: *game_state.current() == GameState::GameOver,
+        acked,
+        ships: ship_query
+            .iter()
+            .map(|(player, transform)| ShipState {
+                player: player.id,
+                translation: transform.translation.truncate().into(),
+                angle: quat_to_angle(&transform.rotation),
+                velocity: player.velocity.into(),
+            })
+            .collect(),
+        asteroids: asteroid_query
+            .iter()
+            .map(|(entity, transform)| AsteroidState {
+                id: entity.to_bits(),
+                translation: transform.translation.truncate().into(),
+                angle: quat_to_angle(&transform.rotation),
+                scale: transform.scale.x,
+            })
+            .collect(),
+        bullets: bullet_query
+            .iter()
+            .map(|(entity, bullet, transform)| BulletState {
+                id: entity.to_bits(),
+                translation: transform.translation.truncate().into(),
+                angle: quat_to_angle(&transform.rotation),
+                owner: bullet.owner,
+                weapon: bullet.weapon,
+            })
+            .collect(),
+        power_ups: power_up_query
+            .iter()
+            .map(|(entity, power_up, transform)| PowerUpState {
+                id: entity.to_bits(),
+                translation: transform.translation.truncate().into(),
+                kind: power_up.kind,
+            })
+            .collect(),
+        scoreboard: scoreboard.clone(),
+        match_time_left: (match_timer.0.duration() - match_timer.0.elapsed()).as_secs_f32(),
+    });
+
+    for client in server.clients.iter() {
+        send(&server.socket, client.addr, &snapshot);
+    }
+}
+
+fn reset_lobby_timer(mut lobby_timer: ResMut<LobbyTimer>) {
+    lobby_timer.0.reset();
+}
+
+// Keeps sending the final results for a little while, so clients that miss a
+// packet still find out the game is over, then opens the seats up again
+fn return_to_lobby(
+    time: Res<Time>,
+    mut server: ResMut<Server>,
+    mut lobby_timer: ResMut<LobbyTimer>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if lobby_timer.0.tick(time.delta()).just_finished() {
+        info!("game over, waiting for players");
+        server.clients.clear();
+        game_state.set(GameState::Menu).unwrap();
+    }
+}
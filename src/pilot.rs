@@ -0,0 +1,299 @@
+// Anything that can fly a ship: the local controls, the built-in bot, or an
+// autopilot being trained or benchmarked. Each tick a ship's `Pilot` is shown
+// what's around the ship and picks the ship's `ShipInput`.
+
+use std::f32::consts::PI;
+
+use crate::controls::{Devices, ShipInput};
+use crate::game::{
+    asteroid_velocity, quat_to_angle, Asteroid, Bullet, Hyperspace, Player, Scoreboard,
+    BULLET_SPEED,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// How far from its ship a pilot can see asteroids, bullets and other ships
+pub const OBSERVATION_RANGE: f32 = 500.0;
+
+pub trait ShipController: Send + Sync + 'static {
+    fn act(&mut self, observation: &Observation, devices: &Devices) -> ShipInput;
+}
+
+// Flies the ship it's attached to
+#[derive(Component)]
+pub struct Pilot(pub Box<dyn ShipController>);
+
+impl Pilot {
+    pub fn new(controller: impl ShipController) -> Self {
+        Pilot(Box::new(controller))
+    }
+}
+
+// What a pilot sees. Other objects are given relative to the ship, the short
+// way round the screen edges, and sorted nearest first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Observation {
+    pub player: usize,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    // Radians anticlockwise from pointing right
+    pub heading: f32,
+    pub hyperspace_ready: bool,
+    pub lives: i32,
+    pub score: i32,
+    pub asteroids: Vec<AsteroidObservation>,
+    pub bullets: Vec<BulletObservation>,
+    // The other ships
+    pub ships: Vec<ShipObservation>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AsteroidObservation {
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    pub size: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BulletObservation {
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    pub owner: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShipObservation {
+    pub player: usize,
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    pub heading: f32,
+}
+
+// Builds what the given player's ship can see, from everything in the game
+pub fn observe(
+    player: usize,
+    hyperspace_ready: bool,
+    ships: &[(Player, Transform)],
+    asteroids: &[(Asteroid, Transform)],
+    bullets: &[(Bullet, Transform)],
+    scoreboard: &Scoreboard,
+) -> Observation {
+    let (ship, transform) = match ships.iter().find(|(ship, _)| ship.id == player) {
+        Some(ship) => ship,
+        None => return Observation::default(),
+    };
+    let position = transform.translation.truncate();
+    let score = scoreboard.players.get(player);
+
+    let mut observation = Observation {
+        player,
+        position,
+        velocity: ship.velocity,
+        heading: quat_to_angle(&transform.rotation),
+        hyperspace_ready,
+        lives: score.map_or(0, |score| score.lives),
+        score: score.map_or(0, |score| score.points),
+        asteroids: asteroids
+            .iter()
+            .map(|(asteroid, transform)| AsteroidObservation {
+                offset: wrapped_offset(position, transform),
+                velocity: asteroid_velocity(asteroid, transform),
+                size: asteroid.size,
+            })
+            .filter(|asteroid| asteroid.offset.length() <= OBSERVATION_RANGE)
+            .collect(),
+        bullets: bullets
+            .iter()
+            .map(|(bullet, transform)| {
+                let angle = quat_to_angle(&transform.rotation);
+                BulletObservation {
+                    offset: wrapped_offset(position, transform),
+                    velocity: Vec2::new(angle.cos(), angle.sin()) * bullet.speed,
+                    owner: bullet.owner,
+                }
+            })
+            .filter(|bullet| bullet.offset.length() <= OBSERVATION_RANGE)
+            .collect(),
+        ships: ships
+            .iter()
+            .filter(|(other, _)| other.id != player)
+            .map(|(other, transform)| ShipObservation {
+                player: other.id,
+                offset: wrapped_offset(position, transform),
+                velocity: other.velocity,
+                heading: quat_to_angle(&transform.rotation),
+            })
+            .filter(|ship| ship.offset.length() <= OBSERVATION_RANGE)
+            .collect(),
+    };
+
+    let distance = |offset: &Vec2| offset.length();
+    observation
+        .asteroids
+        .sort_by(|a, b| distance(&a.offset).total_cmp(&distance(&b.offset)));
+    observation
+        .bullets
+        .sort_by(|a, b| distance(&a.offset).total_cmp(&distance(&b.offset)));
+    observation
+        .ships
+        .sort_by(|a, b| distance(&a.offset).total_cmp(&distance(&b.offset)));
+
+    observation
+}
+
+// Builds a player's observation straight from a world, e.g. a headless game
+pub fn observe_world(world: &mut World, player: usize) -> Observation {
+    let ships: Vec<(Player, Transform)> = world
+        .query::<(&Player, &Transform)>()
+        .iter(world)
+        .map(|(player, transform)| (player.clone(), *transform))
+        .collect();
+    let asteroids: Vec<(Asteroid, Transform)> = world
+        .query::<(&Asteroid, &Transform)>()
+        .iter(world)
+        .map(|(asteroid, transform)| (asteroid.clone(), *transform))
+        .collect();
+    let bullets: Vec<(Bullet, Transform)> = world
+        .query::<(&Bullet, &Transform)>()
+        .iter(world)
+        .map(|(bullet, transform)| (bullet.clone(), *transform))
+        .collect();
+    let hyperspace_ready = world
+        .query::<(&Player, &Hyperspace)>()
+        .iter(world)
+        .any(|(ship, hyperspace)| ship.id == player && hyperspace.ready());
+
+    observe(
+        player,
+        hyperspace_ready,
+        &ships,
+        &asteroids,
+        &bullets,
+        world.get_resource::<Scoreboard>().unwrap(),
+    )
+}
+
+// The shortest way from `from` to the object, which may be across a screen edge
+fn wrapped_offset(from: Vec2, transform: &Transform) -> Vec2 {
+    let mut offset = transform.translation.truncate() - from;
+
+    if offset.x > 640.0 {
+        offset.x -= 1280.0;
+    } else if offset.x < -640.0 {
+        offset.x += 1280.0;
+    }
+
+    if offset.y > 360.0 {
+        offset.y -= 720.0;
+    } else if offset.y < -360.0 {
+        offset.y += 720.0;
+    }
+
+    offset
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn drive_ships(
+    keys: Option<Res<Input<KeyCode>>>,
+    buttons: Option<Res<Input<GamepadButton>>>,
+    axes: Option<Res<Axis<GamepadAxis>>>,
+    scoreboard: Res<Scoreboard>,
+    ship_query: Query<(&Player, &Transform)>,
+    asteroid_query: Query<(&Asteroid, &Transform)>,
+    bullet_query: Query<(&Bullet, &Transform)>,
+    mut pilot_query: Query<(&Player, &Hyperspace, &mut Pilot, &mut ShipInput)>,
+) {
+    if pilot_query.is_empty() {
+        return;
+    }
+
+    let devices = Devices {
+        keys: keys.as_deref(),
+        buttons: buttons.as_deref(),
+        axes: axes.as_deref(),
+    };
+
+    let ships: Vec<(Player, Transform)> = ship_query
+        .iter()
+        .map(|(player, transform)| (player.clone(), *transform))
+        .collect();
+    let asteroids: Vec<(Asteroid, Transform)> = asteroid_query
+        .iter()
+        .map(|(asteroid, transform)| (asteroid.clone(), *transform))
+        .collect();
+    let bullets: Vec<(Bullet, Transform)> = bullet_query
+        .iter()
+        .map(|(bullet, transform)| (bullet.clone(), *transform))
+        .collect();
+
+    for (player, hyperspace, mut pilot, mut input) in pilot_query.iter_mut() {
+        let observation = observe(
+            player.id,
+            hyperspace.ready(),
+            &ships,
+            &asteroids,
+            &bullets,
+            &scoreboard,
+        );
+        *input = pilot.0.act(&observation, &devices);
+    }
+}
+
+// A simple autopilot. It turns towards the nearest asteroid or enemy ship,
+// aiming ahead of it, and shoots once lined up. It backs away from anything
+// that gets too close, and jumps to hyperspace when a hit is about to land.
+pub struct HeuristicBot;
+
+impl ShipController for HeuristicBot {
+    fn act(&mut self, observation: &Observation, _devices: &Devices) -> ShipInput {
+        let mut input = ShipInput::default();
+
+        // Both kinds of target as (offset, velocity, radius)
+        let target = observation
+            .asteroids
+            .iter()
+            .map(|asteroid| {
+                (
+                    asteroid.offset,
+                    asteroid.velocity,
+                    asteroid.size as f32 * 16.0,
+                )
+            })
+            .chain(
+                observation
+                    .ships
+                    .iter()
+                    .map(|ship| (ship.offset, ship.velocity, 16.0)),
+            )
+            .min_by(|a, b| a.0.length().total_cmp(&b.0.length()));
+
+        let (offset, velocity, radius) = match target {
+            Some(target) => target,
+            None => return input,
+        };
+
+        // Aim where the target will be when the bullet gets there
+        let lead = offset + velocity * (offset.length() / BULLET_SPEED);
+        let mut error = lead.y.atan2(lead.x) - observation.heading;
+        if error > PI {
+            error -= 2.0 * PI;
+        } else if error < -PI {
+            error += 2.0 * PI;
+        }
+
+        input.left = error > 0.05;
+        input.right = error < -0.05;
+        input.fire = error.abs() < 0.2;
+
+        let gap = offset.length() - radius;
+        let closing = velocity.dot(-offset.normalize_or_zero()) > 0.0;
+
+        if gap < 48.0 && closing && observation.hyperspace_ready {
+            input.hyperspace = true;
+        } else if gap < 150.0 {
+            input.reverse = true;
+        }
+
+        input
+    }
+}
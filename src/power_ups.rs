@@ -0,0 +1,271 @@
+// Power-ups, dropped now and then where an asteroid is shot down. They drift
+// and wrap around like asteroids until a ship flies into one or it runs out of
+// time. Most give the ship that picks them up an effect for a while, listed
+// under its score in the HUD, and some a new weapon.
+
+use std::f32::consts::PI;
+
+use crate::game::{
+    wrap_position, FrameTime, GameMode, GameRng, Player, Roster, Scoreboard, Target,
+    TargetDestroyed,
+};
+use crate::weapons::{WeaponKind, Weapons};
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Chance of a power-up for each asteroid shot down
+const DROP_CHANCE: f64 = 0.05;
+
+// Seconds a power-up stays in play if nobody picks it up
+const POWER_UP_LIFETIME: f32 = 10.0;
+
+const POWER_UP_SPEED: f32 = 60.0;
+
+const POWER_UP_SIZE: f32 = 24.0;
+
+// How close a ship's centre has to come to a power-up's to pick it up
+const PICK_UP_DISTANCE: f32 = 28.0;
+
+// How many times faster a ship fires with rapid fire, whatever its weapon
+pub const RAPID_FIRE_RATE: f32 = 3.0;
+
+pub const SCORE_MULTIPLIER: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    // Gives the ship the weapon and switches to it
+    Weapon(WeaponKind),
+    RapidFire,
+    // Asteroids and bullets can't hurt the ship
+    Shield,
+    ExtraLife,
+    // Points scored are doubled
+    ScoreMultiplier,
+}
+
+impl PowerUpKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::Weapon(weapon) => weapon.name(),
+            PowerUpKind::RapidFire => "RAPID",
+            PowerUpKind::Shield => "SHIELD",
+            PowerUpKind::ExtraLife => "1UP",
+            PowerUpKind::ScoreMultiplier => "2X",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PowerUpKind::Weapon(WeaponKind::Single) => Color::WHITE,
+            PowerUpKind::Weapon(WeaponKind::Spread) => Color::YELLOW,
+            PowerUpKind::Weapon(WeaponKind::Laser) => Color::GREEN,
+            PowerUpKind::Weapon(WeaponKind::Missile) => Color::SILVER,
+            PowerUpKind::Weapon(WeaponKind::Mine) => Color::TEAL,
+            PowerUpKind::RapidFire => Color::RED,
+            PowerUpKind::Shield => Color::BLUE,
+            PowerUpKind::ExtraLife => Color::PINK,
+            PowerUpKind::ScoreMultiplier => Color::PURPLE,
+        }
+    }
+
+    // Seconds the effect lasts, or `None` for one that's used up as soon as
+    // it's picked up
+    fn duration(self) -> Option<f32> {
+        match self {
+            PowerUpKind::Weapon(_) | PowerUpKind::ExtraLife => None,
+            PowerUpKind::Shield => Some(8.0),
+            _ => Some(12.0),
+        }
+    }
+}
+
+// The power-ups that can drop in a game. Lives and points only count for
+// something outside versus, and lives only in modes that have them.
+fn drop_table(roster: &Roster, mode: GameMode) -> Vec<PowerUpKind> {
+    let mut kinds = vec![
+        PowerUpKind::Weapon(WeaponKind::Spread),
+        PowerUpKind::Weapon(WeaponKind::Laser),
+        PowerUpKind::Weapon(WeaponKind::Missile),
+        PowerUpKind::Weapon(WeaponKind::Mine),
+        PowerUpKind::RapidFire,
+        PowerUpKind::Shield,
+    ];
+    if !roster.versus {
+        kinds.push(PowerUpKind::ScoreMultiplier);
+        if mode.has_lives() {
+            kinds.push(PowerUpKind::ExtraLife);
+        }
+    }
+
+    kinds
+}
+
+#[derive(Component, Clone)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub velocity: Vec2,
+    pub lifetime: Timer,
+}
+
+// The power-up effects a ship has running, with the time each has left
+#[derive(Component, Clone, Default)]
+pub struct Effects(pub Vec<(PowerUpKind, Timer)>);
+
+impl Effects {
+    pub fn has(&self, kind: PowerUpKind) -> bool {
+        self.0.iter().any(|(active, _)| *active == kind)
+    }
+
+    // Starts an effect, or starts it over if it's already running
+    fn grant(&mut self, kind: PowerUpKind, seconds: f32) {
+        self.0.retain(|(active, _)| *active != kind);
+        self.0.push((kind, Timer::from_seconds(seconds, false)));
+    }
+
+    // The HUD line for the effects, e.g. "SPREAD 7  SHIELD 3"
+    pub fn describe(&self) -> String {
+        self.0
+            .iter()
+            .map(|(kind, timer)| {
+                let left = (timer.duration() - timer.elapsed()).as_secs_f32().ceil();
+                format!("{} {}", kind.name(), left as u32)
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+// How a power-up is drawn, here and wherever a game is mirrored
+pub fn power_up_sprite(kind: PowerUpKind, translation: Vec3) -> SpriteBundle {
+    SpriteBundle {
+        transform: Transform {
+            translation,
+            // Turned to stand on a corner, so it doesn't look like a bullet
+            rotation: Quat::from_rotation_z(PI / 4.0),
+            ..Default::default()
+        },
+        sprite: Sprite {
+            color: kind.color(),
+            custom_size: Some(Vec2::splat(POWER_UP_SIZE)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub(crate) fn add_power_up(commands: &mut Commands, power_up: PowerUp, translation: Vec3) {
+    commands
+        .spawn_bundle(power_up_sprite(power_up.kind, translation))
+        .insert(power_up);
+}
+
+pub(crate) fn drop_power_ups(
+    mut commands: Commands,
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    mut rng: ResMut<GameRng>,
+    mut target_destroyed_event: EventReader<TargetDestroyed>,
+) {
+    let rng = &mut rng.0;
+    let kinds = drop_table(&roster, *mode);
+
+    // Asteroids come in whatever order they were hit in, so they're sorted
+    // first to draw the same numbers for the same ones every time
+    let mut destroyed: Vec<(i32, usize, Vec3)> = target_destroyed_event
+        .iter()
+        .filter_map(|event| match event.target {
+            Target::Asteroid { size } => Some((size, event.player, event.translation)),
+            _ => None,
+        })
+        .collect();
+    destroyed.sort_by_key(|(size, player, translation)| {
+        (
+            translation.x.to_bits(),
+            translation.y.to_bits(),
+            *size,
+            *player,
+        )
+    });
+
+    for (_, _, translation) in destroyed {
+        if !rng.gen_bool(DROP_CHANCE) {
+            continue;
+        }
+
+        let heading = rng.gen_range(-PI..PI);
+        add_power_up(
+            &mut commands,
+            PowerUp {
+                kind: *kinds.choose(rng).unwrap(),
+                velocity: Vec2::new(heading.cos(), heading.sin()) * POWER_UP_SPEED,
+                lifetime: Timer::from_seconds(POWER_UP_LIFETIME, false),
+            },
+            translation,
+        );
+    }
+}
+
+pub(crate) fn power_up_movement(
+    mut commands: Commands,
+    time: Res<FrameTime>,
+    mut query: Query<(Entity, &mut PowerUp, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut power_up, mut transform) in query.iter_mut() {
+        if power_up.lifetime.tick(time.delta).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        transform.translation += (power_up.velocity * dt).extend(0.0);
+        wrap_position(&mut transform.translation);
+    }
+}
+
+pub(crate) fn pick_up_power_ups(
+    mut commands: Commands,
+    mut scoreboard: ResMut<Scoreboard>,
+    power_up_query: Query<(Entity, &PowerUp, &Transform)>,
+    mut ship_query: Query<(&Player, &Transform, &mut Effects, &mut Weapons)>,
+) {
+    for (power_up_entity, power_up, power_up_transform) in power_up_query.iter() {
+        // When ships arrive together the lowest numbered player gets it, so
+        // the game plays out the same however the ships are stored
+        let ship = ship_query
+            .iter_mut()
+            // Not `collide`, which misses a power-up the ship covers completely
+            .filter(|(_, ship_transform, _, _)| {
+                let offset = ship_transform.translation - power_up_transform.translation;
+                offset.truncate().length() < PICK_UP_DISTANCE
+            })
+            .min_by_key(|(player, _, _, _)| player.id);
+
+        let (player, _, mut effects, mut weapons) = match ship {
+            Some(ship) => ship,
+            None => continue,
+        };
+
+        commands.entity(power_up_entity).despawn_recursive();
+
+        match (power_up.kind, power_up.kind.duration()) {
+            (_, Some(seconds)) => effects.grant(power_up.kind, seconds),
+            (PowerUpKind::Weapon(weapon), None) => weapons.pick_up(weapon),
+            (_, None) => {
+                if let Some(score) = scoreboard.players.get_mut(player.id) {
+                    score.lives += 1;
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn tick_effects(time: Res<FrameTime>, mut query: Query<&mut Effects>) {
+    for mut effects in query.iter_mut() {
+        effects
+            .0
+            .retain_mut(|(_, timer)| !timer.tick(time.delta).finished());
+    }
+}
@@ -0,0 +1,414 @@
+// Lets bots written in any language fly ships. The game listens on a local TCP
+// port and talks JSON, one message per line. Every frame, each connected bot is
+// sent what its ship can see and answers with the buttons to hold down:
+//
+//     <- {"type":"welcome","player":1,"lockstep":true}
+//     <- {"type":"observation","frame":0,"observation":{"player":1,"position":[0.0,0.0],...}}
+//     -> {"type":"action","thrust":true,"fire":true}
+//     <- {"type":"game_over","scoreboard":{"players":[...]}}
+//
+// Buttons left out of an action aren't pressed. Bots take the seats for
+// `players` in the order they connect. In lock-step mode every frame is the
+// same length, and the game pauses until every seat is filled and every bot
+// has answered before playing each frame, so bots can take as long as they
+// like to think. Otherwise the game runs at its own pace, a ship holds its
+// last action until the next, and a bot that falls behind on reading misses
+// frames until it catches up. Either way the window keeps drawing.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::controls::{Devices, ShipInput};
+use crate::game::{FrameTime, GameSystem, Player, Scoreboard, FIXED_FRAME_RATE};
+use crate::pilot::{observe_world, Observation, Pilot, ShipController};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+pub struct RemotePlugin {
+    pub address: SocketAddr,
+    // Player ids flown by remote bots
+    pub players: Vec<usize>,
+    pub lockstep: bool,
+}
+
+impl Plugin for RemotePlugin {
+    fn build(&self, app: &mut App) {
+        let listener = TcpListener::bind(self.address).expect("failed to bind remote bot socket");
+        listener
+            .set_nonblocking(true)
+            .expect("failed to make remote bot socket non-blocking");
+        let address = listener
+            .local_addr()
+            .expect("failed to read remote bot socket address");
+        info!("waiting for remote bots on {}", address);
+
+        if self.lockstep {
+            app.world
+                .get_resource_or_insert_with(FrameTime::default)
+                .fixed = Some(Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE));
+            app.add_system_to_stage(CoreStage::PreUpdate, hold_for_bots)
+                .add_system_to_stage(CoreStage::PostUpdate, ask_bots.exclusive_system());
+        }
+
+        app.insert_resource(RemoteAddress(address))
+            .insert_resource(RemoteBots {
+                listener,
+                lockstep: self.lockstep,
+                seats: self
+                    .players
+                    .iter()
+                    .map(|player| (*player, Seat::default()))
+                    .collect(),
+            })
+            .add_system(accept_bots.before(GameSystem::Input))
+            .add_system_set(
+                SystemSet::on_update(GameState::Game).with_system(
+                    attach_remote_pilots
+                        .after("attach_controls")
+                        .before(GameSystem::Input),
+                ),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Game).with_system(send_game_over));
+    }
+}
+
+// Where the game is listening for bots, which is only known once it has bound
+// when `address` asks for any free port
+pub struct RemoteAddress(pub SocketAddr);
+
+// Sent from the game to a bot
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameMessage {
+    Welcome {
+        player: usize,
+        lockstep: bool,
+    },
+    Observation {
+        // Frames since the game started
+        frame: u64,
+        observation: Observation,
+    },
+    GameOver {
+        scoreboard: Scoreboard,
+    },
+}
+
+// Sent from a bot to the game
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Action(ShipInput),
+}
+
+// A connected bot
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    // Whatever has arrived of the next message
+    line: String,
+    // Messages waiting to go out, for when the bot is slow to read them
+    outgoing: Vec<u8>,
+    frame: u64,
+    action: ShipInput,
+    // In lock-step mode, whether the bot has been sent the next frame, and
+    // whether it has answered
+    asked: bool,
+    answered: bool,
+}
+
+impl Connection {
+    fn send(&mut self, message: &GameMessage) -> io::Result<()> {
+        serde_json::to_writer(&mut self.outgoing, message)?;
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    // Sends as much of what's waiting as the bot has room for, without waiting
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.writer.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(sent) => {
+                    self.outgoing.drain(..sent);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    fn observe(&mut self, observation: &Observation) -> io::Result<()> {
+        self.send(&GameMessage::Observation {
+            frame: self.frame,
+            observation: observation.clone(),
+        })?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    // Like `observe`, but a bot that's still behind on reading earlier frames
+    // skips this one. Only the latest frame matters when the game isn't
+    // waiting for answers.
+    fn observe_latest(&mut self, observation: &Observation) -> io::Result<()> {
+        self.flush()?;
+        if self.outgoing.is_empty() {
+            self.observe(observation)
+        } else {
+            self.frame += 1;
+            Ok(())
+        }
+    }
+
+    // Picks up everything the bot has sent so far, and sends on whatever it
+    // now has room for, without waiting for more
+    fn poll(&mut self) -> io::Result<()> {
+        self.flush()?;
+
+        loop {
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(_) if self.line.ends_with('\n') => {
+                    let message = serde_json::from_str(&self.line);
+                    self.line.clear();
+
+                    match message {
+                        Ok(BotMessage::Action(action)) => {
+                            self.action = action;
+                            self.answered = true;
+                        }
+                        Err(error) => warn!("ignoring bad message from remote bot: {}", error),
+                    }
+                }
+                // Only part of a line so far
+                Ok(_) => {}
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+// One remote player's connection, if a bot has taken it, shared between the
+// listener and the player's ship
+type Seat = Arc<Mutex<Option<Connection>>>;
+
+struct RemoteBots {
+    listener: TcpListener,
+    lockstep: bool,
+    seats: Vec<(usize, Seat)>,
+}
+
+// Marks ships already handed to a remote bot
+#[derive(Component)]
+struct RemoteShip;
+
+// Flies a ship by asking the bot in its seat, or does nothing while the seat is empty
+struct RemotePilot {
+    player: usize,
+    seat: Seat,
+    lockstep: bool,
+}
+
+impl ShipController for RemotePilot {
+    fn act(&mut self, observation: &Observation, _devices: &Devices) -> ShipInput {
+        let mut seat = self.seat.lock().unwrap();
+        let connection = match seat.as_mut() {
+            Some(connection) => connection,
+            None => return ShipInput::default(),
+        };
+
+        // `hold_for_bots` has already waited for the answer to this frame
+        if self.lockstep {
+            connection.asked = false;
+            connection.answered = false;
+            return connection.action;
+        }
+
+        match connection
+            .observe_latest(observation)
+            .and_then(|_| connection.poll())
+        {
+            Ok(()) => connection.action,
+            Err(error) => {
+                info!("remote bot for player {} left: {}", self.player + 1, error);
+                *seat = None;
+                ShipInput::default()
+            }
+        }
+    }
+}
+
+fn accept_bots(bots: Res<RemoteBots>) {
+    let empty = |(_, seat): &&(usize, Seat)| seat.lock().unwrap().is_none();
+
+    while let Some((player, seat)) = bots.seats.iter().find(empty) {
+        let stream = match bots.listener.accept() {
+            Ok((stream, address)) => {
+                info!("remote bot {} is flying player {}", address, player + 1);
+                stream
+            }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return,
+            Err(error) => {
+                warn!("failed to accept remote bot: {}", error);
+                return;
+            }
+        };
+
+        let connection = stream
+            .set_nodelay(true)
+            .and_then(|_| stream.set_nonblocking(true))
+            .and_then(|_| stream.try_clone())
+            .map(|writer| Connection {
+                reader: BufReader::new(stream),
+                writer,
+                line: String::new(),
+                outgoing: Vec::new(),
+                frame: 0,
+                action: ShipInput::default(),
+                asked: false,
+                answered: false,
+            })
+            .and_then(|mut connection| {
+                connection.send(&GameMessage::Welcome {
+                    player: *player,
+                    lockstep: bots.lockstep,
+                })?;
+                Ok(connection)
+            });
+
+        match connection {
+            Ok(connection) => *seat.lock().unwrap() = Some(connection),
+            Err(error) => warn!("failed to set up remote bot: {}", error),
+        }
+    }
+}
+
+// In lock-step mode, sends each remote ship's bot what it can see at the start
+// of the next frame, unless it's still thinking about the last one. This runs
+// once the frame is over, so bots can think while it's drawn.
+fn ask_bots(world: &mut World) {
+    let playing = world
+        .get_resource::<State<GameState>>()
+        .is_some_and(|state| matches!(state.current(), GameState::Game | GameState::Paused));
+    if !playing {
+        return;
+    }
+
+    let players: Vec<usize> = world
+        .query_filtered::<&Player, With<RemoteShip>>()
+        .iter(world)
+        .map(|player| player.id)
+        .collect();
+    let seats = world.get_resource::<RemoteBots>().unwrap().seats.clone();
+
+    for (player, seat) in seats.iter().filter(|(id, _)| players.contains(id)) {
+        let mut seat = seat.lock().unwrap();
+        let connection = match seat.as_mut() {
+            Some(connection) if !connection.asked => connection,
+            _ => continue,
+        };
+
+        let observation = observe_world(world, *player);
+        match connection.observe(&observation) {
+            Ok(()) => connection.asked = true,
+            Err(error) => {
+                info!("remote bot for player {} left: {}", player + 1, error);
+                *seat = None;
+            }
+        }
+    }
+}
+
+// In lock-step mode, pauses the game until every remote ship's seat is filled
+// and its bot has answered, then carries on
+fn hold_for_bots(
+    bots: Res<RemoteBots>,
+    mut game_state: ResMut<State<GameState>>,
+    query: Query<&Player, With<RemoteShip>>,
+) {
+    let mut ready = true;
+    let mut empty = Vec::new();
+
+    for player in query.iter() {
+        let seat = match bots.seats.iter().find(|(id, _)| *id == player.id) {
+            Some((_, seat)) => seat,
+            None => continue,
+        };
+        let mut seat = seat.lock().unwrap();
+
+        ready &= match seat.as_mut().map(|connection| connection.poll()) {
+            Some(Ok(())) => seat.as_ref().unwrap().answered,
+            Some(Err(error)) => {
+                info!("remote bot for player {} left: {}", player.id + 1, error);
+                *seat = None;
+                empty.push(player.id);
+                false
+            }
+            None => {
+                empty.push(player.id);
+                false
+            }
+        };
+    }
+
+    match game_state.current() {
+        GameState::Game if !ready => {
+            for player in empty {
+                info!("waiting for a remote bot to fly player {}", player + 1);
+            }
+            let _ = game_state.push(GameState::Paused);
+        }
+        GameState::Paused if ready => {
+            let _ = game_state.pop();
+        }
+        _ => {}
+    }
+}
+
+// Replaces the local pilot that `ControlsPlugin` gives every new ship
+fn attach_remote_pilots(
+    mut commands: Commands,
+    bots: Res<RemoteBots>,
+    query: Query<(Entity, &Player), Without<RemoteShip>>,
+) {
+    for (entity, player) in query.iter() {
+        if let Some((_, seat)) = bots.seats.iter().find(|(id, _)| *id == player.id) {
+            commands
+                .entity(entity)
+                .insert(Pilot::new(RemotePilot {
+                    player: player.id,
+                    seat: seat.clone(),
+                    lockstep: bots.lockstep,
+                }))
+                .insert(RemoteShip);
+        }
+    }
+}
+
+fn send_game_over(bots: Res<RemoteBots>, scoreboard: Res<Scoreboard>) {
+    for (_, seat) in bots.seats.iter() {
+        let mut seat = seat.lock().unwrap();
+
+        if let Some(connection) = seat.as_mut() {
+            let message = GameMessage::GameOver {
+                scoreboard: scoreboard.clone(),
+            };
+            if connection.send(&message).is_err() {
+                *seat = None;
+                continue;
+            }
+            // The next game counts its frames from zero again
+            connection.frame = 0;
+            connection.asked = false;
+            connection.answered = false;
+        }
+    }
+}
@@ -0,0 +1,514 @@
+// Recorded games. A game plays out the same way again given its seed, its
+// rules and what every ship did on every frame, so that's all a replay keeps:
+// each frame's length and each ship's input. Watching one means playing the
+// game again in a headless app fed from the recording.
+//
+// Every local game is recorded and saved to `replays/` when it ends.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::controls::ShipInput;
+use crate::game::{
+    headless_game, time_limit, AsteroidPhysics, Difficulty, FrameTime, GameMode, GameSeed,
+    GameSnapshot, GameSystem, Player, Roster, RunStats, Scoreboard, VersusRules, MAX_FRAME_TIME,
+    MIN_FRAME_TIME,
+};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const REPLAY_DIR: &str = "replays";
+
+const REPLAY_EXTENSION: &str = "replay";
+
+// Bumped whenever a change to the game would make old replays play out
+// differently
+const REPLAY_VERSION: u32 = 3;
+
+// Frames between the snapshots a `ReplayPlayer` keeps for seeking
+const SNAPSHOT_INTERVAL: usize = 120;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub delta: Duration,
+    // By player id
+    pub inputs: Vec<ShipInput>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    version: u32,
+    pub seed: u64,
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+    pub roster: Roster,
+    pub asteroid_physics: bool,
+    // Seconds since 1970 in UTC
+    pub recorded_at: u64,
+    pub frames: Vec<ReplayFrame>,
+    // How the game ended
+    pub scoreboard: Scoreboard,
+    pub wave: u32,
+}
+
+impl Replay {
+    // A recording of a game that's about to start, with no frames yet
+    pub fn new(
+        seed: u64,
+        mode: GameMode,
+        difficulty: Difficulty,
+        roster: Roster,
+        asteroid_physics: bool,
+    ) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            mode,
+            difficulty,
+            roster,
+            asteroid_physics,
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs()),
+            frames: Vec::new(),
+            scoreboard: Scoreboard {
+                players: Vec::new(),
+            },
+            wave: 0,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let bytes = fs::read(path).map_err(|error| error.to_string())?;
+        Replay::from_bytes(&bytes)
+    }
+
+    // A replay from the contents of a replay file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, String> {
+        let replay: Replay = bincode::deserialize(bytes).map_err(|error| error.to_string())?;
+
+        if replay.version != REPLAY_VERSION {
+            return Err(format!(
+                "recorded by version {} of the game, this is version {}",
+                replay.version, REPLAY_VERSION
+            ));
+        }
+
+        Ok(replay)
+    }
+
+    // Writes the replay to a new file in `REPLAY_DIR`, named after when it
+    // was recorded, and returns where
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let name = if self.roster.versus {
+            "versus"
+        } else {
+            self.mode.key()
+        };
+        let path = Path::new(REPLAY_DIR).join(format!(
+            "{}-{}.{}",
+            self.recorded_at, name, REPLAY_EXTENSION
+        ));
+
+        let bytes = self.to_bytes()?;
+        fs::create_dir_all(REPLAY_DIR).map_err(|error| error.to_string())?;
+        fs::write(&path, bytes).map_err(|error| error.to_string())?;
+
+        Ok(path)
+    }
+
+    // The contents of a replay file
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        bincode::serialize(self).map_err(|error| error.to_string())
+    }
+
+    // What was played, leaving out when and how it's said to have ended, so
+    // two copies of the same run match however they were changed after
+    pub fn run_bytes(&self) -> Result<Vec<u8>, String> {
+        let run = (
+            self.seed,
+            self.mode,
+            self.difficulty,
+            &self.roster,
+            self.asteroid_physics,
+            &self.frames,
+        );
+        bincode::serialize(&run).map_err(|error| error.to_string())
+    }
+
+    pub fn points(&self) -> i32 {
+        self.scoreboard
+            .players
+            .iter()
+            .map(|score| score.points)
+            .sum()
+    }
+
+    // How long the game lasted
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.delta).sum()
+    }
+
+    // Plays the replay again with the game's own systems and checks that it
+    // ends the way it says it did. Returns how the run went if it did, or
+    // every difference found.
+    pub fn verify(&self) -> Result<RunStats, Vec<String>> {
+        self.check_frames().map_err(|error| vec![error])?;
+
+        let mut player = ReplayPlayer::new(self.clone());
+        let mut mismatches = Vec::new();
+
+        while player.step() {
+            // Inputs for a game that's already over can't have been played
+            if player.is_over() && !player.is_finished() {
+                mismatches.push(format!(
+                    "the game ended after frame {} of {}",
+                    player.frame(),
+                    player.len()
+                ));
+                break;
+            }
+        }
+
+        let snapshot = player.snapshot();
+        let claimed = &self.scoreboard.players;
+        let played = &snapshot.scoreboard.players;
+        if claimed.len() != played.len() {
+            mismatches.push(format!(
+                "players: claimed {}, played {}",
+                claimed.len(),
+                played.len()
+            ));
+        }
+
+        for (id, (claimed, played)) in claimed.iter().zip(played).enumerate() {
+            for (what, claimed, played) in [
+                ("points", claimed.points, played.points),
+                ("lives", claimed.lives, played.lives),
+                ("frags", claimed.frags, played.frags),
+            ] {
+                if claimed != played {
+                    mismatches.push(format!(
+                        "player {} {}: claimed {}, played {}",
+                        id + 1,
+                        what,
+                        claimed,
+                        played
+                    ));
+                }
+            }
+        }
+
+        if self.wave != snapshot.stats.wave {
+            mismatches.push(format!(
+                "wave: claimed {}, played {}",
+                self.wave, snapshot.stats.wave
+            ));
+        }
+
+        if mismatches.is_empty() {
+            Ok(snapshot.stats)
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    // Checks that every frame is one the game could have played, before
+    // anything is played. Forged frames could otherwise stretch a run out
+    // to score more time, or stop the clock altogether.
+    fn check_frames(&self) -> Result<(), String> {
+        for (index, frame) in self.frames.iter().enumerate() {
+            if frame.delta < MIN_FRAME_TIME || frame.delta > MAX_FRAME_TIME {
+                return Err(format!(
+                    "frame {} lasts {:?}, but frames last from {:?} to {:?}",
+                    index + 1,
+                    frame.delta,
+                    MIN_FRAME_TIME,
+                    MAX_FRAME_TIME
+                ));
+            }
+        }
+
+        // Replays are played by the default rules
+        let limit = time_limit(&self.roster, &VersusRules::default(), self.mode);
+        if let (Some(limit), Some(last)) = (limit, self.frames.last()) {
+            // The game ends on the frame that runs the clock out
+            if (self.duration() - last.delta).as_secs_f32() >= limit {
+                return Err(format!(
+                    "the game lasts {:.1}s, past its {}s time limit",
+                    self.duration().as_secs_f32(),
+                    limit
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The saved replays, newest first
+pub fn list() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(REPLAY_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == REPLAY_EXTENSION))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Names start with the time they were recorded
+    paths.sort();
+    paths.reverse();
+    paths
+}
+
+// Records every game and saves it once it's over
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>()
+            .init_resource::<LastReplay>()
+            .add_system_set(SystemSet::on_enter(GameState::Game).with_system(start_recording))
+            .add_system_set(
+                SystemSet::on_update(GameState::Game).with_system(
+                    record_frame
+                        .after(GameSystem::Input)
+                        .before(GameSystem::Fire),
+                ),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(save_recording.label("save_replay")),
+            );
+    }
+}
+
+// The game being recorded
+#[derive(Default)]
+struct Recording(Option<Replay>);
+
+// The last game recorded, from when it ends
+#[derive(Default)]
+pub struct LastReplay(pub Option<Replay>);
+
+fn start_recording(
+    roster: Res<Roster>,
+    mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    seed: Res<GameSeed>,
+    physics: Res<AsteroidPhysics>,
+    mut recording: ResMut<Recording>,
+) {
+    recording.0 = Some(Replay::new(
+        seed.0,
+        *mode,
+        *difficulty,
+        roster.clone(),
+        physics.enabled,
+    ));
+}
+
+fn record_frame(
+    time: Res<FrameTime>,
+    mut recording: ResMut<Recording>,
+    ships: Query<(&Player, &ShipInput)>,
+) {
+    if let Some(replay) = &mut recording.0 {
+        // Ships waiting to respawn aren't doing anything
+        let mut inputs = vec![ShipInput::default(); replay.roster.players];
+        for (player, input) in ships.iter() {
+            if let Some(slot) = inputs.get_mut(player.id) {
+                *slot = *input;
+            }
+        }
+
+        replay.frames.push(ReplayFrame {
+            delta: time.delta,
+            inputs,
+        });
+    }
+}
+
+fn save_recording(
+    scoreboard: Res<Scoreboard>,
+    stats: Res<RunStats>,
+    mut recording: ResMut<Recording>,
+    mut last: ResMut<LastReplay>,
+) {
+    if let Some(mut replay) = recording.0.take() {
+        replay.scoreboard = scoreboard.clone();
+        replay.wave = stats.wave;
+
+        match replay.save() {
+            Ok(path) => info!("saved replay {}", path.display()),
+            Err(error) => warn!("failed to save replay: {}", error),
+        }
+
+        last.0 = Some(replay);
+    }
+}
+
+// The inputs for the frame being played back, by player id
+struct Playback(Vec<ShipInput>);
+
+fn play_back(playback: Res<Playback>, mut ships: Query<(&Player, &mut ShipInput)>) {
+    for (player, mut input) in ships.iter_mut() {
+        *input = playback.0.get(player.id).copied().unwrap_or_default();
+    }
+}
+
+// A headless game set up like the recorded one, which plays the recording
+// back through a `ReplayPlayer`
+fn replay_game(replay: &Replay) -> App {
+    let mut app = headless_game(replay.seed, replay.roster.clone());
+    app.insert_resource(replay.mode)
+        .insert_resource(replay.difficulty)
+        .insert_resource(AsteroidPhysics {
+            enabled: replay.asteroid_physics,
+            ..Default::default()
+        })
+        .insert_resource(Playback(Vec::new()))
+        .add_system_set(
+            SystemSet::on_update(GameState::Game)
+                .with_system(play_back.label(GameSystem::Input).before(GameSystem::Fire)),
+        );
+
+    app
+}
+
+// Plays a replay frame by frame. Any frame can be jumped to: going back
+// starts again from the nearest snapshot before it, which are taken every
+// `SNAPSHOT_INTERVAL` frames on the way through.
+pub struct ReplayPlayer {
+    replay: Replay,
+    app: App,
+    // Frames played so far
+    frame: usize,
+    // The state after every `SNAPSHOT_INTERVAL` frames played so far, from
+    // the first interval on
+    snapshots: Vec<GameSnapshot>,
+    // When each frame starts, and the last one ends
+    start_times: Vec<Duration>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        let mut start_times = vec![Duration::ZERO];
+        for frame in replay.frames.iter() {
+            start_times.push(*start_times.last().unwrap() + frame.delta);
+        }
+
+        ReplayPlayer {
+            app: replay_game(&replay),
+            replay,
+            frame: 0,
+            snapshots: Vec::new(),
+            start_times,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    // How many frames there are to play
+    pub fn len(&self) -> usize {
+        self.replay.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.frames.is_empty()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.len()
+    }
+
+    // Whether the game played back has ended
+    pub fn is_over(&self) -> bool {
+        self.app
+            .world
+            .get_resource::<State<GameState>>()
+            .is_some_and(|state| *state.current() != GameState::Game)
+    }
+
+    // How far into the game the given frame starts
+    pub fn time_at(&self, frame: usize) -> Duration {
+        self.start_times[frame.min(self.len())]
+    }
+
+    // The frame being played at the given time into the game
+    pub fn frame_at(&self, time: Duration) -> usize {
+        let frame = self.start_times.partition_point(|start| *start <= time);
+        frame.saturating_sub(1).min(self.len())
+    }
+
+    // The length of the next frame, unless the replay is over
+    pub fn next_delta(&self) -> Option<Duration> {
+        self.replay.frames.get(self.frame).map(|frame| frame.delta)
+    }
+
+    // Plays the next frame. Returns false if there isn't one.
+    pub fn step(&mut self) -> bool {
+        let frame = match self.replay.frames.get(self.frame) {
+            Some(frame) => frame,
+            None => return false,
+        };
+
+        let world = &mut self.app.world;
+        world.get_resource_mut::<FrameTime>().unwrap().fixed = Some(frame.delta);
+        world.insert_resource(Playback(frame.inputs.clone()));
+
+        self.app.update();
+        self.frame += 1;
+
+        if self.frame == (self.snapshots.len() + 1) * SNAPSHOT_INTERVAL {
+            self.snapshots.push(GameSnapshot::save(&mut self.app.world));
+        }
+
+        true
+    }
+
+    pub fn seek(&mut self, target: usize) {
+        let target = target.min(self.len());
+        let nearest = (target / SNAPSHOT_INTERVAL).min(self.snapshots.len());
+
+        // Going forward, a snapshot only helps if it's further on than here
+        if target < self.frame || nearest * SNAPSHOT_INTERVAL > self.frame {
+            self.load(nearest);
+        }
+
+        while self.frame < target {
+            self.step();
+        }
+    }
+
+    // Starts again from the start of the game or, counting from one, the
+    // given snapshot
+    fn load(&mut self, snapshot: usize) {
+        // A game that's over can't be started again, so there's always a
+        // fresh one to put the snapshot in. It has to be through its first
+        // frame, which sets the game up.
+        self.app = replay_game(&self.replay);
+        self.frame = 0;
+
+        if snapshot > 0 {
+            self.step();
+            self.snapshots[snapshot - 1].restore(&mut self.app.world);
+            self.frame = snapshot * SNAPSHOT_INTERVAL;
+        }
+    }
+
+    // The game as it stands, for drawing
+    pub fn snapshot(&mut self) -> GameSnapshot {
+        GameSnapshot::save(&mut self.app.world)
+    }
+}
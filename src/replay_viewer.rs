@@ -0,0 +1,342 @@
+// Watching a saved replay, picked on the Replays page. The replay plays in a
+// `ReplayPlayer` of its own and is drawn the way the attract demo is. The
+// file is only ever read, so nothing done here can change it.
+//
+//     SPACE          pause
+//     UP / DOWN      faster / slower
+//     LEFT / RIGHT   back / on five seconds
+//     , / .          back / on one frame
+//     ESC            back to the list
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::game::{mirror_snapshot, GameAssets, MirrorSprite};
+use crate::replay::{Replay, ReplayPlayer};
+use crate::{despawn_screen, GameState};
+use bevy::prelude::*;
+
+const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 2;
+
+const SEEK_SECONDS: u64 = 5;
+
+// At most this many frames are played per frame drawn, so a slow machine
+// falls behind instead of grinding to a halt
+const MAX_STEPS: usize = 32;
+
+pub struct ReplayViewerPlugin;
+
+impl Plugin for ReplayViewerPlugin {
+    fn build(&self, app: &mut App) {
+        // The player holds a whole bevy app, which can't be shared between threads
+        app.insert_non_send_resource(Viewer {
+            player: None,
+            paused: false,
+            speed: NORMAL_SPEED,
+            owed: Duration::ZERO,
+        })
+        .init_resource::<WatchReplay>()
+        .init_resource::<GameAssets>()
+        .add_system_set(SystemSet::on_enter(GameState::Playback).with_system(open_replay))
+        .add_system_set(
+            SystemSet::on_update(GameState::Playback)
+                .with_system(viewer_controls.label("viewer_controls"))
+                .with_system(play_replay.label("play_replay").after("viewer_controls"))
+                .with_system(viewer_text.after("play_replay")),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Playback)
+                .with_system(close_replay)
+                .with_system(despawn_screen::<ViewerEntity>),
+        );
+    }
+}
+
+// The replay file to watch next
+#[derive(Default)]
+pub struct WatchReplay(pub Option<PathBuf>);
+
+struct Viewer {
+    player: Option<ReplayPlayer>,
+    paused: bool,
+    // Index into `SPEEDS`
+    speed: usize,
+    // Game time that should have been played by now but hasn't yet
+    owed: Duration,
+}
+
+// Anything drawn for the replay, removed when it's left
+#[derive(Component, Clone)]
+struct ViewerEntity;
+
+#[derive(Component)]
+struct ViewerText;
+
+fn open_replay(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    watch: Res<WatchReplay>,
+    mut viewer: NonSendMut<Viewer>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let replay = match watch.0.as_deref().map(Replay::load) {
+        Some(Ok(replay)) => replay,
+        Some(Err(error)) => {
+            warn!("can't play replay: {}", error);
+            let _ = game_state.set(GameState::Replays);
+            return;
+        }
+        None => {
+            let _ = game_state.set(GameState::Replays);
+            return;
+        }
+    };
+
+    *viewer = Viewer {
+        player: Some(ReplayPlayer::new(replay)),
+        paused: false,
+        speed: NORMAL_SPEED,
+        owed: Duration::ZERO,
+    };
+
+    // Filled in by `viewer_text`
+    let style = TextStyle {
+        font: assets.font.clone(),
+        font_size: 24.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: String::new(),
+                        style: style.clone(),
+                    },
+                    TextSection {
+                        value: String::new(),
+                        style: TextStyle {
+                            color: Color::GRAY,
+                            ..style.clone()
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ViewerText)
+        .insert(ViewerEntity);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "[SPACE] PAUSE   [UP/DOWN] SPEED   [LEFT/RIGHT] SEEK   [, .] STEP   [ESC] BACK",
+                TextStyle {
+                    color: Color::GRAY,
+                    font_size: 18.0,
+                    ..style
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ViewerEntity);
+}
+
+fn close_replay(mut viewer: NonSendMut<Viewer>) {
+    viewer.player = None;
+}
+
+fn viewer_controls(
+    mut keys: ResMut<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut viewer: NonSendMut<Viewer>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let back = keys.just_released(KeyCode::Escape)
+        || gamepad_buttons
+            .get_just_released()
+            .any(|button| button.1 == GamepadButtonType::East);
+    if back {
+        // The list would take the same press as going back to the title screen
+        keys.clear_just_released(KeyCode::Escape);
+        let _ = game_state.set(GameState::Replays);
+        return;
+    }
+
+    let Viewer {
+        player,
+        paused,
+        speed,
+        owed,
+    } = &mut *viewer;
+    let player = match player {
+        Some(player) => player,
+        None => return,
+    };
+
+    if keys.just_pressed(KeyCode::Space) {
+        *paused = !*paused;
+    }
+
+    if keys.just_pressed(KeyCode::Up) {
+        *speed = (*speed + 1).min(SPEEDS.len() - 1);
+    } else if keys.just_pressed(KeyCode::Down) {
+        *speed = speed.saturating_sub(1);
+    }
+
+    let seek = Duration::from_secs(SEEK_SECONDS);
+    let now = player.time_at(player.frame());
+    if keys.just_pressed(KeyCode::Left) {
+        player.seek(player.frame_at(now.saturating_sub(seek)));
+        *owed = Duration::ZERO;
+    } else if keys.just_pressed(KeyCode::Right) {
+        player.seek(player.frame_at(now + seek));
+        *owed = Duration::ZERO;
+    }
+
+    // Stepping pauses, so the frame stepped to stays on screen
+    if keys.just_pressed(KeyCode::Comma) {
+        *paused = true;
+        player.seek(player.frame().saturating_sub(1));
+    } else if keys.just_pressed(KeyCode::Period) {
+        *paused = true;
+        player.step();
+    }
+}
+
+fn play_replay(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    mut viewer: NonSendMut<Viewer>,
+    sprite_query: Query<(Entity, &MirrorSprite), With<ViewerEntity>>,
+) {
+    let Viewer {
+        player,
+        paused,
+        speed,
+        owed,
+    } = &mut *viewer;
+    let player = match player {
+        Some(player) => player,
+        None => return,
+    };
+
+    if !*paused {
+        *owed += time.delta().mul_f32(SPEEDS[*speed]);
+
+        let mut steps = 0;
+        while let Some(delta) = player.next_delta() {
+            if *owed < delta || steps == MAX_STEPS {
+                break;
+            }
+
+            *owed -= delta;
+            player.step();
+            steps += 1;
+        }
+
+        if steps == MAX_STEPS || player.is_finished() {
+            *owed = Duration::ZERO;
+        }
+    }
+
+    mirror_snapshot(
+        &mut commands,
+        &assets,
+        &player.snapshot(),
+        sprite_query
+            .iter()
+            .map(|(entity, sprite)| (entity, *sprite)),
+        ViewerEntity,
+    );
+}
+
+fn viewer_text(mut viewer: NonSendMut<Viewer>, mut query: Query<&mut Text, With<ViewerText>>) {
+    let Viewer {
+        player,
+        paused,
+        speed,
+        ..
+    } = &mut *viewer;
+    let player = match player {
+        Some(player) => player,
+        None => return,
+    };
+
+    let replay = player.replay();
+    let status = if player.is_finished() {
+        "  END"
+    } else if *paused {
+        "  PAUSED"
+    } else {
+        ""
+    };
+    let heading = format!(
+        "{}  {} / {}  FRAME {} / {}  {}x{}\n",
+        if replay.roster.versus {
+            "VERSUS"
+        } else {
+            replay.mode.name()
+        },
+        clock_text(player.time_at(player.frame())),
+        clock_text(replay.duration()),
+        player.frame(),
+        player.len(),
+        SPEEDS[*speed],
+        status
+    );
+
+    // The same as the in-game display shows
+    let versus = replay.roster.versus;
+    let lives = !versus && replay.mode.has_lives();
+    let scores = player
+        .snapshot()
+        .scoreboard
+        .players
+        .iter()
+        .enumerate()
+        .map(|(id, score)| match (versus, lives) {
+            (true, _) => format!("P{} FRAGS: {}", id + 1, score.frags),
+            (false, true) => format!(
+                "P{} SCORE: {}  LIVES: {}",
+                id + 1,
+                score.points,
+                score.lives.max(0)
+            ),
+            (false, false) => format!("P{} SCORE: {}", id + 1, score.points),
+        })
+        .collect::<Vec<_>>()
+        .join("   ");
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = heading.clone();
+        text.sections[1].value = scores.clone();
+    }
+}
+
+fn clock_text(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
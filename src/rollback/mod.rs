@@ -0,0 +1,299 @@
+// Peer-to-peer versus play with rollback. Both peers run the whole game and
+// only send each other their inputs. For frames where the other peer's input
+// hasn't arrived yet, a peer guesses that they kept doing what they did last.
+// When the real input turns out different, the peer puts the game back to the
+// frame it guessed wrong and plays it forward again with what it now knows.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::controls::ShipInput;
+use crate::game::{headless_game, GameSnapshot, Player, Roster, FIXED_FRAME_RATE};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod peer;
+
+// How many frames a peer may run past the last input it has from the other
+// peer before it stops and waits for them
+pub const MAX_PREDICTION: u32 = 8;
+
+// Most frames played to catch up in one go, so a long hitch doesn't stall the
+// peer while it plays them all
+const MAX_CATCH_UP: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+pub enum PeerMessage {
+    // Sent until the other peer answers. The lower seed gets player 1 and
+    // seeds the game.
+    Hello {
+        seed: u64,
+    },
+    // The sender's inputs from `start` on. Every message repeats all the
+    // inputs the receiver hasn't confirmed, so a lost message costs nothing
+    // once the next one gets through.
+    Inputs {
+        start: u32,
+        inputs: Vec<ShipInput>,
+        // How many of the receiver's inputs the sender has so far
+        received: u32,
+    },
+}
+
+#[derive(Default)]
+pub struct RollbackStats {
+    pub rollbacks: u32,
+    // Frames played a second time because of a wrong guess
+    pub resimulated: u32,
+}
+
+// One peer's copy of a 1v1 versus match
+pub struct RollbackSession {
+    app: App,
+    seed: u64,
+    local_player: usize,
+    // The next frame to simulate
+    frame: u32,
+    local_inputs: Vec<ShipInput>,
+    // The other peer's inputs, for every frame up to the first one missing
+    remote_inputs: Vec<ShipInput>,
+    // The remote input each frame was simulated with, real or guessed
+    simulated_remote: Vec<ShipInput>,
+    // How many of our inputs the other peer has
+    remote_received: u32,
+    // The earliest frame simulated with a wrong guess
+    rollback_to: Option<u32>,
+    // The state at the start of every frame that might still be played again
+    snapshots: VecDeque<(u32, GameSnapshot)>,
+    // Checksums of the state at the start of each frame, by frame, once
+    // nothing can change it any more
+    checksums: Vec<u64>,
+    pub stats: RollbackStats,
+}
+
+impl RollbackSession {
+    pub fn new(seed: u64, local_player: usize) -> Self {
+        RollbackSession {
+            app: build_app(seed),
+            seed,
+            local_player,
+            frame: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            simulated_remote: Vec::new(),
+            remote_received: 0,
+            rollback_to: None,
+            snapshots: VecDeque::new(),
+            checksums: Vec::new(),
+            stats: RollbackStats::default(),
+        }
+    }
+
+    pub fn local_player(&self) -> usize {
+        self.local_player
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    // Whether another frame can be played without getting too far ahead of
+    // the other peer
+    pub fn can_advance(&self) -> bool {
+        self.frame < self.remote_inputs.len() as u32 + MAX_PREDICTION
+    }
+
+    // Plays one frame with the given local input, after catching up on any
+    // wrong guesses. Returns false, without using the input, if the session
+    // has to wait for the other peer.
+    pub fn advance(&mut self, input: ShipInput) -> bool {
+        self.sync();
+
+        if !self.can_advance() {
+            return false;
+        }
+
+        self.local_inputs.push(input);
+        self.simulate();
+        true
+    }
+
+    // Plays a frame for every whole frame of match time `owed`, taking off
+    // what it plays, so the match keeps to the clock however often this is
+    // called. Time spent waiting for the other peer isn't made up afterwards.
+    // Returns how many frames were played.
+    pub fn advance_for(&mut self, owed: &mut Duration, input: ShipInput) -> u32 {
+        let frame_time = Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE);
+
+        let mut played = 0;
+        while *owed >= frame_time && played < MAX_CATCH_UP {
+            if !self.advance(input) {
+                break;
+            }
+            *owed -= frame_time;
+            played += 1;
+        }
+
+        if played == 0 {
+            self.sync();
+        }
+        if *owed >= frame_time {
+            *owed = Duration::ZERO;
+        }
+        played
+    }
+
+    // Replays any frames that were guessed wrong, without playing a new one
+    pub fn sync(&mut self) {
+        if let Some(target) = self.rollback_to.take() {
+            let end = self.frame;
+            let index = self
+                .snapshots
+                .iter()
+                .position(|(frame, _)| *frame == target)
+                .expect("no snapshot to roll back to");
+
+            // Replaying saves these frames again
+            let snapshot = self.snapshots[index].1.clone();
+            self.snapshots.truncate(index);
+            self.load(&snapshot);
+
+            self.frame = target;
+            while self.frame < end {
+                self.simulate();
+            }
+
+            self.stats.rollbacks += 1;
+            self.stats.resimulated += end - target;
+        }
+
+        // Snapshots from before the first guessed frame are never needed again
+        let confirmed = self.remote_inputs.len() as u32;
+        while matches!(self.snapshots.front(), Some((frame, _)) if *frame < confirmed) {
+            let (_, snapshot) = self.snapshots.pop_front().unwrap();
+            self.checksums.push(snapshot.checksum());
+        }
+    }
+
+    pub fn receive(&mut self, message: PeerMessage) {
+        if let PeerMessage::Inputs {
+            start,
+            inputs,
+            received,
+        } = message
+        {
+            self.remote_received = self.remote_received.max(received);
+
+            // Something in between is still missing, it'll come round again
+            if start > self.remote_inputs.len() as u32 {
+                return;
+            }
+
+            for (frame, input) in (start..).zip(inputs) {
+                if frame < self.remote_inputs.len() as u32 {
+                    continue;
+                }
+                self.remote_inputs.push(input);
+
+                if frame < self.frame && self.simulated_remote[frame as usize] != input {
+                    self.rollback_to = Some(self.rollback_to.map_or(frame, |f| f.min(frame)));
+                }
+            }
+        }
+    }
+
+    // The message to send the other peer after each frame
+    pub fn outgoing(&self) -> PeerMessage {
+        let start = self.remote_received.min(self.local_inputs.len() as u32);
+
+        PeerMessage::Inputs {
+            start,
+            inputs: self.local_inputs[start as usize..].to_vec(),
+            received: self.remote_inputs.len() as u32,
+        }
+    }
+
+    // Whether every frame played so far was played with both real inputs
+    pub fn is_confirmed(&self) -> bool {
+        self.rollback_to.is_none() && self.remote_inputs.len() as u32 >= self.frame
+    }
+
+    // Whether the match has ended, and no late input can change that
+    pub fn is_over(&mut self) -> bool {
+        self.is_confirmed() && self.snapshot().over
+    }
+
+    // The game as it stands, for drawing
+    pub fn snapshot(&mut self) -> GameSnapshot {
+        GameSnapshot::save(&mut self.app.world)
+    }
+
+    pub fn checksum(&mut self) -> u64 {
+        self.snapshot().checksum()
+    }
+
+    // Checksums of the start of every frame both peers agree on, by frame
+    pub fn checksums(&self) -> &[u64] {
+        &self.checksums
+    }
+
+    fn simulate(&mut self) {
+        let frame = self.frame as usize;
+
+        // Guess that the other player is still doing what they last did
+        let remote = match self.remote_inputs.get(frame) {
+            Some(input) => *input,
+            None => self.remote_inputs.last().copied().unwrap_or_default(),
+        };
+        self.simulated_remote.truncate(frame);
+        self.simulated_remote.push(remote);
+
+        self.snapshots
+            .push_back((self.frame, GameSnapshot::save(&mut self.app.world)));
+
+        let mut inputs = [remote; 2];
+        inputs[self.local_player] = self.local_inputs[frame];
+
+        let world = &mut self.app.world;
+        for (player, mut input) in world.query::<(&Player, &mut ShipInput)>().iter_mut(world) {
+            *input = inputs[player.id];
+        }
+
+        self.app.update();
+        self.frame += 1;
+    }
+
+    fn load(&mut self, snapshot: &GameSnapshot) {
+        // Leaving the game despawns everything and there's no way back into
+        // it, so a match that ended on a wrong guess starts over from scratch
+        // before the snapshot goes in
+        let over = *self
+            .app
+            .world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+            != GameState::Game;
+        if over && !snapshot.over {
+            self.app = build_app(self.seed);
+        }
+
+        snapshot.restore(&mut self.app.world);
+    }
+}
+
+// A headless game for one peer, already through its first frame, which sets
+// the match up with nobody touching the controls
+fn build_app(seed: u64) -> App {
+    let mut app = headless_game(
+        seed,
+        Roster {
+            players: 2,
+            versus: true,
+        },
+    );
+
+    app.update();
+    app
+}
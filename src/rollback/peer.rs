@@ -0,0 +1,271 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use super::{PeerMessage, RollbackSession};
+use crate::controls::{read_controls, ControlScheme, Controls, ShipInput};
+use crate::game::{
+    mirror_snapshot, scoreboard_update, spawn_hud, GameAssets, GameMode, Hud, MatchTimer,
+    MirrorSprite, Roster, RunStats, Scoreboard, VersusRules,
+};
+use crate::net::{receive, send, TIMEOUT_SECONDS};
+use crate::{despawn_screen, GameState};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+// Plays a 1v1 versus match straight against another copy of the game. The
+// match runs inside a `RollbackSession`; this plugin feeds it local input,
+// passes messages to and from the other peer and draws the session's state.
+pub struct RollbackPeerPlugin {
+    pub bind: SocketAddr,
+    pub peer: SocketAddr,
+}
+
+impl Plugin for RollbackPeerPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind(self.bind).expect("failed to bind peer socket");
+        socket
+            .set_nonblocking(true)
+            .expect("failed to make peer socket non-blocking");
+
+        // The session holds a whole bevy app, which can't be shared between threads
+        app.insert_non_send_resource(Peer {
+            socket,
+            address: self.peer,
+            seed: rand::random(),
+            session: None,
+            hello_timer: Timer::from_seconds(0.5, true),
+            last_heard: 0.0,
+            owed: Duration::ZERO,
+        })
+        .insert_resource(Roster {
+            players: 2,
+            versus: true,
+        })
+        .init_resource::<VersusRules>()
+        .init_resource::<GameAssets>()
+        .init_resource::<ControlScheme>()
+        .init_resource::<GameMode>()
+        .init_resource::<RunStats>()
+        .insert_resource(Scoreboard {
+            players: Vec::new(),
+        })
+        .insert_resource(MatchTimer(Timer::from_seconds(0.0, false)))
+        .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(waiting_setup))
+        .add_system_set(SystemSet::on_update(GameState::Menu).with_system(handshake))
+        .add_system_set(
+            SystemSet::on_exit(GameState::Menu).with_system(despawn_screen::<WaitingScreen>),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Game)
+                .with_system(read_controls.label(PeerSystem::Input))
+                .with_system(
+                    step_session
+                        .label(PeerSystem::Step)
+                        .after(PeerSystem::Input),
+                )
+                .with_system(draw_session.label(PeerSystem::Draw).after(PeerSystem::Step))
+                .with_system(scoreboard_update.after(PeerSystem::Draw)),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Game)
+                .with_system(despawn_screen::<PeerEntity>)
+                .with_system(despawn_screen::<Hud>),
+        );
+    }
+}
+
+#[derive(SystemLabel, Clone, Copy, Hash, Debug, PartialEq, Eq)]
+enum PeerSystem {
+    Input,
+    Step,
+    Draw,
+}
+
+struct Peer {
+    socket: UdpSocket,
+    address: SocketAddr,
+    // Picked at random to decide who is player 1 and to seed the game
+    seed: u64,
+    // The match, once both peers have said hello
+    session: Option<RollbackSession>,
+    hello_timer: Timer,
+    last_heard: f64,
+    // Match time that should have been played by now but hasn't yet
+    owed: Duration,
+}
+
+// Anything drawn from the session, removed when the game ends
+#[derive(Component, Clone)]
+struct PeerEntity;
+
+#[derive(Component)]
+struct WaitingScreen;
+
+fn waiting_setup(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    peer: NonSend<Peer>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    // Back from the results screen: the match is over
+    if peer.session.is_some() {
+        app_exit_events.send(AppExit);
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                format!("WAITING FOR {}", peer.address),
+                TextStyle {
+                    font: assets.font.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(WaitingScreen);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handshake(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    roster: Res<Roster>,
+    rules: Res<VersusRules>,
+    scheme: Res<ControlScheme>,
+    mut peer: NonSendMut<Peer>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let hello = PeerMessage::Hello { seed: peer.seed };
+
+    if peer.hello_timer.tick(time.delta()).just_finished() {
+        send(&peer.socket, peer.address, &hello);
+    }
+
+    for (from, message) in receive::<PeerMessage>(&peer.socket) {
+        let seed = match message {
+            PeerMessage::Hello { seed } if from == peer.address && seed != peer.seed => seed,
+            _ => continue,
+        };
+
+        // Answer straight away, in case our earlier hellos got lost
+        send(&peer.socket, peer.address, &hello);
+
+        let local_player = if peer.seed < seed { 0 } else { 1 };
+        info!(
+            "connected to {} as player {}",
+            peer.address,
+            local_player + 1
+        );
+
+        peer.session = Some(RollbackSession::new(peer.seed.min(seed), local_player));
+        peer.last_heard = time.seconds_since_startup();
+
+        // Local input is read into this, whichever seat we have
+        commands
+            .spawn()
+            .insert(Controls::for_player(0, *scheme))
+            .insert(ShipInput::default())
+            .insert(PeerEntity);
+
+        spawn_hud(
+            &mut commands,
+            &assets.font,
+            &roster,
+            rules.time_limit.filter(|_| roster.versus),
+        );
+        game_state.set(GameState::Game).unwrap();
+        return;
+    }
+}
+
+fn step_session(
+    time: Res<Time>,
+    mut peer: NonSendMut<Peer>,
+    mut game_state: ResMut<State<GameState>>,
+    query: Query<&ShipInput, With<Controls>>,
+) {
+    let now = time.seconds_since_startup();
+    let Peer {
+        socket,
+        address,
+        seed,
+        session,
+        last_heard,
+        owed,
+        ..
+    } = &mut *peer;
+
+    let session = match session {
+        Some(session) => session,
+        None => return,
+    };
+
+    for (from, message) in receive::<PeerMessage>(socket) {
+        if from != *address {
+            continue;
+        }
+        *last_heard = now;
+
+        match message {
+            // They haven't heard our answer yet
+            PeerMessage::Hello { .. } => {
+                send(socket, *address, &PeerMessage::Hello { seed: *seed })
+            }
+            message => session.receive(message),
+        }
+    }
+
+    // Plays at a steady 60 frames a second whatever the refresh rate, and
+    // waits for the other peer if we've got too far ahead of them
+    *owed += time.delta();
+    let input = query.iter().next().copied().unwrap_or_default();
+    session.advance_for(owed, input);
+
+    send(socket, *address, &session.outgoing());
+
+    if now - *last_heard > TIMEOUT_SECONDS {
+        warn!("lost contact with {}", address);
+        game_state.set(GameState::GameOver).unwrap();
+    } else if session.is_over() {
+        game_state.set(GameState::GameOver).unwrap();
+    }
+}
+
+fn draw_session(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut peer: NonSendMut<Peer>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut stats: ResMut<RunStats>,
+    mut match_timer: ResMut<MatchTimer>,
+    sprite_query: Query<(Entity, &MirrorSprite), With<PeerEntity>>,
+) {
+    let session = match peer.session.as_mut() {
+        Some(session) => session,
+        None => return,
+    };
+    let snapshot = session.snapshot();
+
+    mirror_snapshot(
+        &mut commands,
+        &assets,
+        &snapshot,
+        sprite_query
+            .iter()
+            .map(|(entity, sprite)| (entity, *sprite)),
+        PeerEntity,
+    );
+
+    *scoreboard = snapshot.scoreboard.clone();
+    *stats = snapshot.stats.clone();
+    match_timer.0 = snapshot.match_timer.clone();
+}
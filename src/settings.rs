@@ -0,0 +1,409 @@
+// Player settings: the window and gameplay options. They're
+// read from a settings file at startup, can be changed on the settings screen
+// and are written back when the screen is left.
+
+use std::fs;
+
+use crate::controls::ControlScheme;
+use crate::game::{Difficulty, Hud};
+use crate::{despawn_screen, GameState};
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use serde::{Deserialize, Serialize};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+const RESOLUTIONS: [(f32, f32); 4] = [
+    (1280.0, 720.0),
+    (1600.0, 900.0),
+    (1920.0, 1080.0),
+    (2560.0, 1440.0),
+];
+
+const HUD_SCALES: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DisplayMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    fn window_mode(self) -> WindowMode {
+        match self {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::SizedFullscreen,
+        }
+    }
+}
+
+// Fields missing from the settings file keep their defaults
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub display_mode: DisplayMode,
+    pub resolution: (f32, f32),
+    pub vsync: bool,
+    pub difficulty: Difficulty,
+    pub control_scheme: ControlScheme,
+    pub hud_scale: f32,
+    // Racing the best run on the same seed, see `ghost.rs`
+    pub ghost: bool,
+    // Where finished games are sent, see `leaderboard.rs`. These are only
+    // set in the settings file, e.g. by whoever runs a tournament.
+    pub leaderboard_url: Option<String>,
+    pub player_name: String,
+    // Signs submissions, for servers that want them signed
+    pub leaderboard_key: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            display_mode: DisplayMode::Windowed,
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            difficulty: Difficulty::default(),
+            control_scheme: ControlScheme::default(),
+            hud_scale: 1.0,
+            ghost: true,
+            leaderboard_url: None,
+            player_name: "PLAYER".into(),
+            leaderboard_key: None,
+        }
+    }
+}
+
+impl Settings {
+    // The saved settings, or the defaults if there aren't any yet or they
+    // can't be read
+    pub fn load() -> Self {
+        match fs::read_to_string(SETTINGS_FILE) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|error| {
+                warn!("ignoring unreadable {}: {}", SETTINGS_FILE, error);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|text| fs::write(SETTINGS_FILE, text).map_err(|error| error.to_string()));
+
+        if let Err(error) = result {
+            warn!("failed to save {}: {}", SETTINGS_FILE, error);
+        }
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "Asteroids!".into(),
+            width: self.resolution.0,
+            height: self.resolution.1,
+            vsync: self.vsync,
+            mode: self.display_mode.window_mode(),
+            ..Default::default()
+        }
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .init_resource::<Difficulty>()
+            .init_resource::<ControlScheme>()
+            .insert_resource(SettingsCursor(0))
+            .add_system(apply_settings)
+            .add_system(scale_hud)
+            .add_system_set(SystemSet::on_enter(GameState::Settings).with_system(settings_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(settings_input.label("settings_input"))
+                    .with_system(settings_text.after("settings_input")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Settings)
+                    .with_system(save_settings)
+                    .with_system(despawn_screen::<SettingsScreen>),
+            );
+    }
+}
+
+// Hands the settings on to the resources and window that use them
+fn apply_settings(
+    settings: Res<Settings>,
+    mut difficulty: ResMut<Difficulty>,
+    mut scheme: ResMut<ControlScheme>,
+    mut windows: ResMut<Windows>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    *difficulty = settings.difficulty;
+    *scheme = settings.control_scheme;
+
+    if let Some(window) = windows.get_primary_mut() {
+        let mode = settings.display_mode.window_mode();
+        if window.mode() != mode {
+            window.set_mode(mode);
+        }
+
+        let (width, height) = settings.resolution;
+        if (window.requested_width(), window.requested_height()) != (width, height) {
+            window.set_resolution(width, height);
+        }
+
+        if window.vsync() != settings.vsync {
+            window.set_vsync(settings.vsync);
+        }
+    }
+}
+
+// The HUD is built at normal size wherever the game is played, and grown or
+// shrunk here as it appears
+fn scale_hud(settings: Res<Settings>, mut query: Query<&mut Text, Added<Hud>>) {
+    for mut text in query.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.font_size *= settings.hud_scale;
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    settings.save();
+}
+
+#[derive(Clone, Copy)]
+enum Row {
+    DisplayMode,
+    Resolution,
+    Vsync,
+    Difficulty,
+    Controls,
+    HudScale,
+    Ghost,
+}
+
+const ROWS: [Row; 7] = [
+    Row::DisplayMode,
+    Row::Resolution,
+    Row::Vsync,
+    Row::Difficulty,
+    Row::Controls,
+    Row::HudScale,
+    Row::Ghost,
+];
+
+impl Row {
+    fn text(self, settings: &Settings) -> String {
+        match self {
+            Row::DisplayMode => format!(
+                "DISPLAY: {}",
+                match settings.display_mode {
+                    DisplayMode::Windowed => "WINDOWED",
+                    DisplayMode::Borderless => "BORDERLESS",
+                    DisplayMode::Fullscreen => "FULLSCREEN",
+                }
+            ),
+            Row::Resolution => format!(
+                "RESOLUTION: {} X {}",
+                settings.resolution.0, settings.resolution.1
+            ),
+            Row::Vsync => format!("VSYNC: {}", if settings.vsync { "ON" } else { "OFF" }),
+            Row::Difficulty => format!(
+                "DIFFICULTY: {}",
+                match settings.difficulty {
+                    Difficulty::Easy => "EASY",
+                    Difficulty::Normal => "NORMAL",
+                    Difficulty::Hard => "HARD",
+                }
+            ),
+            Row::Controls => format!(
+                "CONTROLS: {}",
+                match settings.control_scheme {
+                    ControlScheme::Standard => "P1 WASD, P2 ARROWS",
+                    ControlScheme::Swapped => "P1 ARROWS, P2 WASD",
+                }
+            ),
+            Row::HudScale => format!("HUD SIZE: {}%", (settings.hud_scale * 100.0).round()),
+            Row::Ghost => format!("GHOST RACE: {}", if settings.ghost { "ON" } else { "OFF" }),
+        }
+    }
+
+    // Moves the setting one step left or right. Lists wrap round.
+    fn change(self, settings: &mut Settings, step: i32) {
+        fn cycle<T: Copy + PartialEq>(options: &[T], current: T, step: i32) -> T {
+            let index = options.iter().position(|option| *option == current);
+            let index = index.unwrap_or(0) as i32 + step;
+            options[index.rem_euclid(options.len() as i32) as usize]
+        }
+
+        match self {
+            Row::DisplayMode => {
+                settings.display_mode = cycle(
+                    &[
+                        DisplayMode::Windowed,
+                        DisplayMode::Borderless,
+                        DisplayMode::Fullscreen,
+                    ],
+                    settings.display_mode,
+                    step,
+                )
+            }
+            Row::Resolution => settings.resolution = cycle(&RESOLUTIONS, settings.resolution, step),
+            Row::Vsync => settings.vsync = !settings.vsync,
+            Row::Difficulty => {
+                settings.difficulty = cycle(
+                    &[Difficulty::Easy, Difficulty::Normal, Difficulty::Hard],
+                    settings.difficulty,
+                    step,
+                )
+            }
+            Row::Controls => {
+                settings.control_scheme = cycle(
+                    &[ControlScheme::Standard, ControlScheme::Swapped],
+                    settings.control_scheme,
+                    step,
+                )
+            }
+            Row::HudScale => settings.hud_scale = cycle(&HUD_SCALES, settings.hud_scale, step),
+            Row::Ghost => settings.ghost = !settings.ghost,
+        }
+    }
+}
+
+// The selected row
+struct SettingsCursor(usize);
+
+#[derive(Component)]
+struct SettingsScreen;
+
+// The text for the row at this index
+#[derive(Component)]
+struct SettingsRow(usize);
+
+fn settings_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut cursor: ResMut<SettingsCursor>,
+) {
+    let font = asset_server.load("Orbitron.ttf");
+    cursor.0 = 0;
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(SettingsScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(30.0)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Settings",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 60.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            for index in 0..ROWS.len() {
+                parent
+                    .spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(6.0)),
+                            ..Default::default()
+                        },
+                        // Filled in by `settings_text`
+                        text: Text::with_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 30.0,
+                                color: Color::GRAY,
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(SettingsRow(index));
+            }
+
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(30.0)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "[UP] [DOWN] CHOOSE   [LEFT] [RIGHT] CHANGE   [ESC] BACK",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 20.0,
+                        color: Color::GRAY,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn settings_input(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut cursor: ResMut<SettingsCursor>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let pressed = |codes: &[KeyCode]| codes.iter().any(|code| keys.just_pressed(*code));
+
+    if pressed(&[KeyCode::Escape]) {
+        game_state.set(GameState::Menu).unwrap();
+    } else if pressed(&[KeyCode::Up, KeyCode::W]) {
+        cursor.0 = (cursor.0 + ROWS.len() - 1) % ROWS.len();
+    } else if pressed(&[KeyCode::Down, KeyCode::S]) {
+        cursor.0 = (cursor.0 + 1) % ROWS.len();
+    } else if pressed(&[KeyCode::Left, KeyCode::A]) {
+        ROWS[cursor.0].change(&mut settings, -1);
+    } else if pressed(&[KeyCode::Right, KeyCode::D, KeyCode::Return]) {
+        ROWS[cursor.0].change(&mut settings, 1);
+    }
+}
+
+fn settings_text(
+    settings: Res<Settings>,
+    cursor: Res<SettingsCursor>,
+    mut query: Query<(&SettingsRow, &mut Text)>,
+) {
+    for (row, mut text) in query.iter_mut() {
+        let selected = row.0 == cursor.0;
+        let line = ROWS[row.0].text(&settings);
+
+        let section = &mut text.sections[0];
+        section.value = if selected {
+            format!("> {} <", line)
+        } else {
+            line
+        };
+        section.style.color = if selected { Color::WHITE } else { Color::GRAY };
+    }
+}
@@ -0,0 +1,210 @@
+// Ship weapons. Every ship starts with the single shot, gets the others from
+// power-ups and cycles through the ones it has with the weapon key. Each
+// weapon fires at its own rate, and its projectiles remember what fired them,
+// which decides how they move and what becomes of them when they hit.
+
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use crate::controls::ShipInput;
+use crate::game::{quat_to_angle, Asteroid, Bullet, FrameTime, Player, Roster, BULLET_SPEED};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Angle in radians between the bullets of a spread shot
+const SPREAD_ANGLE: f32 = 0.25;
+
+// How fast a missile turns towards its target, in radians per second
+const MISSILE_TURN_RATE: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum WeaponKind {
+    // One bullet straight ahead
+    Single,
+    // Three bullets, fanned out
+    Spread,
+    // Quick bolts that burn through everything they touch, but not for long
+    Laser,
+    // Slower shots that turn towards the nearest target
+    Missile,
+    // Left behind the ship for something to run into
+    Mine,
+}
+
+impl WeaponKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            WeaponKind::Single => "SINGLE",
+            WeaponKind::Spread => "SPREAD",
+            WeaponKind::Laser => "LASER",
+            WeaponKind::Missile => "MISSILE",
+            WeaponKind::Mine => "MINES",
+        }
+    }
+
+    // Seconds between shots
+    pub fn interval(self) -> f32 {
+        match self {
+            WeaponKind::Single => 0.3,
+            WeaponKind::Spread => 0.45,
+            WeaponKind::Laser => 0.08,
+            WeaponKind::Missile => 0.8,
+            WeaponKind::Mine => 1.0,
+        }
+    }
+
+    fn speed(self) -> f32 {
+        match self {
+            WeaponKind::Single | WeaponKind::Spread => BULLET_SPEED,
+            WeaponKind::Laser => 1600.0,
+            WeaponKind::Missile => 450.0,
+            WeaponKind::Mine => 0.0,
+        }
+    }
+
+    // How big its projectiles are, which is also the area they hit
+    pub fn projectile_size(self) -> Vec2 {
+        match self {
+            WeaponKind::Single | WeaponKind::Spread => Vec2::new(12.0, 12.0),
+            WeaponKind::Laser => Vec2::new(20.0, 6.0),
+            WeaponKind::Missile => Vec2::new(16.0, 8.0),
+            WeaponKind::Mine => Vec2::new(20.0, 20.0),
+        }
+    }
+
+    // Seconds its projectiles last, or `None` for ones that fly on until they
+    // hit something
+    fn range(self) -> Option<f32> {
+        match self {
+            WeaponKind::Single | WeaponKind::Spread => None,
+            WeaponKind::Laser => Some(0.3),
+            WeaponKind::Missile => Some(3.0),
+            WeaponKind::Mine => Some(8.0),
+        }
+    }
+
+    // Whether its projectiles carry on through what they hit
+    pub fn pierces(self) -> bool {
+        self == WeaponKind::Laser
+    }
+
+    // The projectiles fired by a ship here, facing this way
+    pub fn fire(self, owner: usize, translation: Vec3, angle: f32) -> Vec<(Bullet, Transform)> {
+        let headings: &[f32] = match self {
+            WeaponKind::Spread => &[-SPREAD_ANGLE, 0.0, SPREAD_ANGLE],
+            _ => &[0.0],
+        };
+
+        headings
+            .iter()
+            .map(|offset| {
+                let bullet = Bullet {
+                    speed: self.speed(),
+                    owner,
+                    weapon: self,
+                    range: self
+                        .range()
+                        .map(|seconds| Timer::from_seconds(seconds, false)),
+                    scored: false,
+                };
+                let transform = Transform {
+                    translation,
+                    rotation: Quat::from_rotation_z(angle + offset),
+                    scale: self.projectile_size().extend(0.0),
+                };
+
+                (bullet, transform)
+            })
+            .collect()
+    }
+}
+
+// The weapons a ship has and the one it's using
+#[derive(Component, Clone)]
+pub struct Weapons {
+    pub current: WeaponKind,
+    // In the order the weapon key goes through them
+    pub owned: Vec<WeaponKind>,
+    // Time until the current weapon can fire again
+    pub(crate) cooldown: Duration,
+    // Whether the weapon key was held last frame, so holding it only switches once
+    pub(crate) switch_held: bool,
+}
+
+impl Default for Weapons {
+    fn default() -> Self {
+        Weapons {
+            current: WeaponKind::Single,
+            owned: vec![WeaponKind::Single],
+            cooldown: Duration::ZERO,
+            switch_held: false,
+        }
+    }
+}
+
+impl Weapons {
+    // Adds a weapon, if the ship doesn't have it already, and switches to it
+    pub fn pick_up(&mut self, weapon: WeaponKind) {
+        if let Err(index) = self.owned.binary_search(&weapon) {
+            self.owned.insert(index, weapon);
+        }
+        self.current = weapon;
+    }
+
+    // Acts on a frame of input and says whether the ship fires. `rate` speeds
+    // up the current weapon's fire rate.
+    pub(crate) fn trigger(&mut self, input: &ShipInput, delta: Duration, rate: f32) -> bool {
+        if input.switch_weapon && !self.switch_held {
+            let index = self.owned.iter().position(|weapon| *weapon == self.current);
+            self.current = self.owned[index.map_or(0, |index| (index + 1) % self.owned.len())];
+        }
+        self.switch_held = input.switch_weapon;
+
+        self.cooldown = self.cooldown.saturating_sub(delta);
+        if !input.fire || !self.cooldown.is_zero() {
+            return false;
+        }
+
+        self.cooldown = Duration::from_secs_f32(self.current.interval() / rate);
+        true
+    }
+}
+
+// Turns each missile towards the nearest asteroid, or in versus the nearest
+// asteroid or other ship
+pub(crate) fn steer_missiles(
+    time: Res<FrameTime>,
+    roster: Res<Roster>,
+    mut missile_query: Query<(&Bullet, &mut Transform)>,
+    asteroid_query: Query<&Transform, (With<Asteroid>, Without<Bullet>)>,
+    ship_query: Query<(&Player, &Transform), Without<Bullet>>,
+) {
+    let max_turn = MISSILE_TURN_RATE * time.delta_seconds();
+
+    for (missile, mut transform) in missile_query.iter_mut() {
+        if missile.weapon != WeaponKind::Missile {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let ships = ship_query
+            .iter()
+            .filter(|(player, _)| roster.versus && player.id != missile.owner)
+            .map(|(_, transform)| transform);
+        let target = asteroid_query
+            .iter()
+            .chain(ships)
+            .map(|target| target.translation.truncate() - position)
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+
+        let offset = match target {
+            Some(offset) if offset != Vec2::ZERO => offset,
+            _ => continue,
+        };
+
+        let angle = quat_to_angle(&transform.rotation);
+        // The way round that's the shorter turn
+        let turn = (offset.y.atan2(offset.x) - angle + PI).rem_euclid(2.0 * PI) - PI;
+        transform.rotation = Quat::from_rotation_z(angle + turn.clamp(-max_turn, max_turn));
+    }
+}
@@ -0,0 +1,167 @@
+use asteroids::game::{
+    headless_game, spawn_point, Asteroid, Bullet, GameMode, GameSnapshot, Roster, ScoreTable,
+    ScoreTables, FIXED_FRAME_RATE,
+};
+use asteroids::weapons::WeaponKind;
+use bevy::prelude::*;
+
+// Where asteroids are set up, well away from the ship in the middle
+fn spot() -> Vec3 {
+    Vec3::new(300.0, 200.0, 0.0)
+}
+
+// An asteroid sitting still at `spot`
+fn asteroid(size: i32) -> (Asteroid, Transform) {
+    let asteroid = Asteroid { speed: 0.0, size };
+    let transform = Transform {
+        translation: spot(),
+        scale: Vec3::splat(size as f32),
+        ..Default::default()
+    };
+
+    (asteroid, transform)
+}
+
+// Fires at the left edge of an asteroid at `spot`, so the projectiles are
+// crossing it when collisions are checked. Something that's already wholly
+// inside an asteroid doesn't hit it.
+fn fire(weapon: WeaponKind, size: i32) -> Vec<(Bullet, Transform)> {
+    let edge = spot() - Vec3::new(size as f32 * 16.0, 0.0, 0.0);
+
+    weapon
+        .fire(0, edge, 0.0)
+        .into_iter()
+        .map(|(bullet, mut transform)| {
+            // Back by as far as it moves in a frame
+            transform.translation -=
+                transform.rotation * Vec3::X * bullet.speed / FIXED_FRAME_RATE as f32;
+            (bullet, transform)
+        })
+        .collect()
+}
+
+// Plays one frame of a game that has nothing in it but the ship and these
+// asteroids and projectiles, and returns how it ends up
+fn play_frame(
+    asteroids: Vec<(Asteroid, Transform)>,
+    bullets: Vec<(Bullet, Transform)>,
+) -> GameSnapshot {
+    play_frame_in(headless_game(1, Roster::default()), asteroids, bullets)
+}
+
+// The same, in a game that hasn't been set up yet
+fn play_frame_in(
+    mut app: App,
+    asteroids: Vec<(Asteroid, Transform)>,
+    bullets: Vec<(Bullet, Transform)>,
+) -> GameSnapshot {
+    app.update();
+
+    let mut snapshot = GameSnapshot::save(&mut app.world);
+    snapshot.asteroids = asteroids;
+    snapshot.bullets = bullets;
+    snapshot.restore(&mut app.world);
+    app.update();
+
+    GameSnapshot::save(&mut app.world)
+}
+
+#[test]
+fn laser_counts_one_hit_however_many_asteroids_it_goes_through() {
+    let after = play_frame(vec![asteroid(3), asteroid(3)], fire(WeaponKind::Laser, 3));
+
+    let stats = &after.stats.players[0];
+    assert_eq!(stats.asteroids_destroyed, [0, 0, 2]);
+    assert_eq!(stats.hits, 1);
+    // Lasers go on through
+    assert_eq!(after.bullets.len(), 1);
+    assert!(after.bullets[0].0.scored);
+}
+
+#[test]
+fn spread_shots_split_an_asteroid_they_all_hit_once() {
+    let after = play_frame(vec![asteroid(3)], fire(WeaponKind::Spread, 3));
+
+    assert_eq!(after.stats.players[0].asteroids_destroyed, [0, 0, 1]);
+    let sizes: Vec<i32> = after
+        .asteroids
+        .iter()
+        .map(|(asteroid, _)| asteroid.size)
+        .collect();
+    assert_eq!(sizes, [2, 2]);
+    // Only the shot that got there first is used up
+    assert_eq!(after.bullets.len(), 2);
+}
+
+#[test]
+fn shot_through_overlapping_asteroids_only_destroys_one() {
+    let after = play_frame(vec![asteroid(3), asteroid(3)], fire(WeaponKind::Single, 3));
+
+    assert_eq!(after.stats.players[0].asteroids_destroyed, [0, 0, 1]);
+    assert_eq!(after.stats.players[0].hits, 1);
+    assert_eq!(after.asteroids.len(), 3);
+    assert!(after.bullets.is_empty());
+}
+
+#[test]
+fn small_asteroids_score_by_the_table_for_the_mode_being_played() {
+    let mut tables = ScoreTables::default();
+    tables.0.insert(
+        GameMode::Survival,
+        ScoreTable {
+            small_asteroid: 7,
+            ..Default::default()
+        },
+    );
+
+    let mut app = headless_game(1, Roster::default());
+    app.insert_resource(GameMode::Survival)
+        .insert_resource(tables);
+    let after = play_frame_in(app, vec![asteroid(1)], fire(WeaponKind::Single, 1));
+
+    assert_eq!(after.stats.players[0].asteroids_destroyed, [1, 0, 0]);
+    assert_eq!(after.scoreboard.players[0].points, 7);
+}
+
+#[test]
+fn ship_shot_by_two_players_at_once_only_dies_once() {
+    let roster = Roster {
+        players: 3,
+        versus: true,
+    };
+    let mut app = headless_game(1, roster.clone());
+    app.update();
+
+    // Player 2 sits at `spot`, with a shot from each of the others crossing
+    // its left edge
+    let mut snapshot = GameSnapshot::save(&mut app.world);
+    snapshot.asteroids.clear();
+    let target = snapshot
+        .ships
+        .iter_mut()
+        .find(|ship| ship.player.id == 1)
+        .unwrap();
+    target.transform.translation = spot();
+    snapshot.bullets = [0, 2]
+        .into_iter()
+        .flat_map(|owner| {
+            let mut bullets = fire(WeaponKind::Single, 1);
+            bullets[0].0.owner = owner;
+            bullets
+        })
+        .collect();
+    snapshot.restore(&mut app.world);
+    app.update();
+
+    let after = GameSnapshot::save(&mut app.world);
+    let frags: Vec<i32> = after
+        .scoreboard
+        .players
+        .iter()
+        .map(|score| score.frags)
+        .collect();
+    assert_eq!(frags.iter().sum::<i32>(), 1, "{:?}", frags);
+    let target = after.ships.iter().find(|ship| ship.player.id == 1).unwrap();
+    assert_eq!(target.transform.translation, spawn_point(1, &roster));
+    assert!(after.bullets.is_empty());
+}
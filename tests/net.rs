@@ -0,0 +1,117 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use asteroids::controls::ShipInput;
+use asteroids::game::{GamePlugin, Roster};
+use asteroids::net::server::{NetServerPlugin, ServerAddress};
+use asteroids::net::{ClientMessage, ServerMessage, Snapshot};
+use asteroids::GameState;
+use bevy::core::CorePlugin;
+use bevy::prelude::*;
+
+fn server(roster: Roster) -> (App, SocketAddr) {
+    let mut app = App::new();
+    app.add_plugin(CorePlugin)
+        .insert_resource(roster)
+        .add_plugin(GamePlugin)
+        .add_plugin(NetServerPlugin {
+            bind: "127.0.0.1:0".parse().unwrap(),
+        })
+        .add_state(GameState::Menu);
+    let address = app.world.get_resource::<ServerAddress>().unwrap().0;
+
+    (app, address)
+}
+
+fn client() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_nonblocking(true).unwrap();
+    socket
+}
+
+fn send(socket: &UdpSocket, to: SocketAddr, message: &ClientMessage) {
+    socket
+        .send_to(&bincode::serialize(message).unwrap(), to)
+        .unwrap();
+}
+
+fn receive(socket: &UdpSocket) -> Vec<ServerMessage> {
+    let mut buffer = [0; 65536];
+    let mut messages = Vec::new();
+    while let Ok(length) = socket.recv(&mut buffer) {
+        messages.push(bincode::deserialize(&buffer[..length]).unwrap());
+    }
+    messages
+}
+
+// Runs the server a frame at a time until the client has been sent something
+// that `accept` takes, or gives up after a few seconds
+fn run_until<T>(
+    app: &mut App,
+    socket: &UdpSocket,
+    mut accept: impl FnMut(ServerMessage) -> Option<T>,
+) -> T {
+    for _ in 0..500 {
+        app.update();
+        std::thread::sleep(Duration::from_millis(5));
+
+        if let Some(found) = receive(socket).into_iter().find_map(&mut accept) {
+            return found;
+        }
+    }
+    panic!("the server never answered");
+}
+
+#[test]
+fn client_joins_and_gets_snapshots_of_its_inputs() {
+    let (mut app, address) = server(Roster {
+        players: 1,
+        versus: false,
+    });
+    let socket = client();
+
+    send(&socket, address, &ClientMessage::Join);
+    let player = run_until(&mut app, &socket, |message| match message {
+        ServerMessage::Welcome { player, .. } => Some(player),
+        _ => None,
+    });
+    assert_eq!(player, 0);
+
+    let input = ShipInput {
+        thrust: true,
+        ..Default::default()
+    };
+    for sequence in 1..=3 {
+        send(&socket, address, &ClientMessage::Input { sequence, input });
+    }
+
+    let snapshot: Snapshot = run_until(&mut app, &socket, |message| match message {
+        ServerMessage::Snapshot(snapshot) if snapshot.acked[0] == 3 => Some(snapshot),
+        _ => None,
+    });
+    assert!(!snapshot.over);
+    assert_eq!(snapshot.ships.len(), 1);
+    assert_eq!(snapshot.ships[0].player, 0);
+}
+
+#[test]
+fn server_turns_clients_away_once_the_game_is_full() {
+    let (mut app, address) = server(Roster {
+        players: 1,
+        versus: false,
+    });
+    let first = client();
+    let second = client();
+
+    send(&first, address, &ClientMessage::Join);
+    run_until(&mut app, &first, |message| match message {
+        ServerMessage::Welcome { .. } => Some(()),
+        _ => None,
+    });
+
+    send(&second, address, &ClientMessage::Join);
+    run_until(&mut app, &second, |message| match message {
+        ServerMessage::Full => Some(()),
+        _ => None,
+    });
+}
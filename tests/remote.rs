@@ -0,0 +1,227 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use asteroids::controls::{ControlsPlugin, ShipInput};
+use asteroids::game::{headless_game, FrameTime, GameMode, Roster, RunStats, FIXED_FRAME_RATE};
+use asteroids::remote::{BotMessage, GameMessage, RemoteAddress, RemotePlugin};
+use asteroids::GameState;
+use bevy::prelude::*;
+
+// Frames the game has played
+#[derive(Default)]
+struct Played(u64);
+
+fn count_frames(mut played: ResMut<Played>) {
+    played.0 += 1;
+}
+
+fn remote_plugin(lockstep: bool) -> RemotePlugin {
+    RemotePlugin {
+        address: "127.0.0.1:0".parse().unwrap(),
+        players: vec![0],
+        lockstep,
+    }
+}
+
+fn game(lockstep: bool) -> (App, SocketAddr) {
+    let mut app = headless_game(7, Roster::default());
+    app.add_plugin(ControlsPlugin)
+        .add_plugin(remote_plugin(lockstep))
+        .init_resource::<Played>()
+        .add_system_set(SystemSet::on_update(GameState::Game).with_system(count_frames));
+    let address = app.world.get_resource::<RemoteAddress>().unwrap().0;
+
+    (app, address)
+}
+
+fn played(app: &App) -> u64 {
+    app.world.get_resource::<Played>().unwrap().0
+}
+
+fn state(app: &App) -> GameState {
+    app.world
+        .get_resource::<State<GameState>>()
+        .unwrap()
+        .current()
+        .clone()
+}
+
+// Runs the game until `done`, or gives up after a few seconds. Every update
+// has to come straight back whether or not the bots have answered.
+fn run_until(app: &mut App, done: impl Fn(&App) -> bool) {
+    for _ in 0..5000 {
+        app.update();
+        if done(app) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("gave up waiting on the game");
+}
+
+// What a bot heard from the game
+#[derive(Default)]
+struct Heard {
+    lockstep: Option<bool>,
+    frames: Vec<u64>,
+}
+
+// A bot that always thrusts and fires. It flies until the game hangs up, or
+// leaves without answering once it hears frame `leave_at` or a later one.
+fn bot(address: SocketAddr, leave_at: Option<u64>) -> JoinHandle<Heard> {
+    let stream = TcpStream::connect(address).unwrap();
+    thread::spawn(move || fly(stream, leave_at))
+}
+
+fn fly(stream: TcpStream, leave_at: Option<u64>) -> Heard {
+    let mut writer = stream.try_clone().unwrap();
+    let mut heard = Heard::default();
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        match serde_json::from_str(&line).unwrap() {
+            GameMessage::Welcome { player, lockstep } => {
+                assert_eq!(player, 0);
+                heard.lockstep = Some(lockstep);
+            }
+            GameMessage::Observation { frame, .. } => {
+                heard.frames.push(frame);
+                if leave_at.is_some_and(|leave_at| frame >= leave_at) {
+                    break;
+                }
+
+                let action = BotMessage::Action(ShipInput {
+                    thrust: true,
+                    fire: true,
+                    ..Default::default()
+                });
+                let mut message = serde_json::to_vec(&action).unwrap();
+                message.push(b'\n');
+                if writer.write_all(&message).is_err() {
+                    break;
+                }
+            }
+            GameMessage::GameOver { .. } => break,
+        }
+    }
+
+    heard
+}
+
+#[test]
+fn bot_flies_a_ship_in_lock_step() {
+    let (mut app, address) = game(true);
+    let bot = bot(address, None);
+
+    // Three seconds
+    run_until(&mut app, |app| played(app) == 180);
+    let shots = app.world.get_resource::<RunStats>().unwrap().players[0].shots_fired;
+    assert!(shots > 0, "the bot's actions never reached its ship");
+
+    // Hanging up lets the bot go
+    drop(app);
+    let heard = bot.join().unwrap();
+    assert_eq!(heard.lockstep, Some(true));
+    // The first frame only sets the game up, and the end of every frame after
+    // that is sent to the bot
+    assert_eq!(heard.frames, (0..180).collect::<Vec<u64>>());
+}
+
+#[test]
+fn game_pauses_while_a_seat_is_empty() {
+    let (mut app, address) = game(true);
+
+    for _ in 0..30 {
+        app.update();
+    }
+    assert_eq!(played(&app), 1);
+    assert_eq!(state(&app), GameState::Paused);
+
+    // Answers 29 frames, then leaves without answering the 30th
+    let leaver = bot(address, Some(29));
+    run_until(&mut app, |app| played(app) == 30);
+    assert_eq!(leaver.join().unwrap().frames.len(), 30);
+
+    for _ in 0..30 {
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(played(&app), 30);
+    assert_eq!(state(&app), GameState::Paused);
+
+    // Another bot takes the seat and the game carries on
+    let _bot = bot(address, None);
+    run_until(&mut app, |app| played(app) == 60);
+}
+
+#[test]
+fn game_runs_at_its_own_pace_without_lock_step() {
+    let (mut app, address) = game(false);
+    let bot = bot(address, None);
+
+    for _ in 0..120 {
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(played(&app), 120);
+
+    drop(app);
+    let heard = bot.join().unwrap();
+    assert_eq!(heard.lockstep, Some(false));
+    assert!(!heard.frames.is_empty());
+    let expected: Vec<u64> = (0..heard.frames.len() as u64).collect();
+    assert_eq!(heard.frames, expected);
+}
+
+#[test]
+fn lock_step_frames_are_all_the_same_length() {
+    let mut app = App::new();
+    app.add_plugin(remote_plugin(true));
+
+    let frame_time = app.world.get_resource::<FrameTime>().unwrap();
+    assert_eq!(
+        frame_time.fixed,
+        Some(Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE))
+    );
+}
+
+#[test]
+fn bot_that_falls_behind_on_reading_keeps_its_seat() {
+    let (mut app, address) = game(false);
+    // Nothing ends it while the bot isn't reading
+    app.insert_resource(GameMode::Zen);
+    let stream = TcpStream::connect(address).unwrap();
+    let (start, started) = mpsc::channel();
+    let bot = thread::spawn(move || {
+        started.recv().unwrap();
+        fly(stream, Some(STALL + 60))
+    });
+
+    // Far more than fits in the socket buffers goes unread
+    for _ in 0..STALL {
+        app.update();
+    }
+    let stats = app.world.get_resource::<RunStats>().unwrap();
+    assert_eq!(stats.players[0].shots_fired, 0);
+
+    start.send(()).unwrap();
+    run_until(&mut app, |_| bot.is_finished());
+    let stats = app.world.get_resource::<RunStats>().unwrap();
+    assert!(stats.players[0].shots_fired > 0);
+
+    // Frames it was too far behind to be sent were skipped, and nothing came
+    // through out of order or cut short
+    let heard = bot.join().unwrap();
+    assert!(heard.frames.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(heard.frames.windows(2).any(|pair| pair[0] + 1 < pair[1]));
+}
+
+// Frames the slow bot doesn't read
+const STALL: u64 = 20_000;
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use asteroids::controls::ShipInput;
+use asteroids::game::{Difficulty, GameMode, Roster, FIXED_FRAME_RATE};
+use asteroids::replay::{Replay, ReplayFrame, ReplayPlayer};
+
+fn frame_time() -> Duration {
+    Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE)
+}
+
+// A recording of a ship that turns, thrusts and fires in a steady pattern.
+// The ending it claims is whatever playing it gives.
+fn replay(mode: GameMode, frames: usize) -> Replay {
+    let mut replay = Replay::new(3, mode, Difficulty::Normal, Roster::default(), false);
+    replay.frames = (0..frames)
+        .map(|frame| ReplayFrame {
+            delta: frame_time(),
+            inputs: vec![ShipInput {
+                thrust: frame % 90 < 30,
+                left: frame % 200 < 40,
+                fire: frame % 20 == 0,
+                ..Default::default()
+            }],
+        })
+        .collect();
+
+    claim_ending(&mut replay);
+    replay
+}
+
+fn claim_ending(replay: &mut Replay) {
+    let mut player = ReplayPlayer::new(replay.clone());
+    while player.step() {}
+
+    let snapshot = player.snapshot();
+    replay.scoreboard = snapshot.scoreboard;
+    replay.wave = snapshot.stats.wave;
+}
+
+fn refusal(replay: &Replay) -> Vec<String> {
+    match replay.verify() {
+        Ok(_) => panic!("the replay verified"),
+        Err(errors) => errors,
+    }
+}
+
+#[test]
+fn honest_replay_verifies() {
+    assert!(replay(GameMode::Survival, 600).verify().is_ok());
+}
+
+#[test]
+fn frames_that_take_no_time_are_refused() {
+    let mut replay = replay(GameMode::Survival, 600);
+    replay.frames[100].delta = Duration::ZERO;
+
+    let errors = refusal(&replay);
+    assert!(errors[0].starts_with("frame 101 lasts"), "{:?}", errors);
+}
+
+#[test]
+fn frames_longer_than_the_game_plays_are_refused() {
+    let mut replay = replay(GameMode::Survival, 600);
+    replay.frames[0].delta = Duration::from_secs(5);
+    claim_ending(&mut replay);
+
+    let errors = refusal(&replay);
+    assert!(errors[0].starts_with("frame 1 lasts"), "{:?}", errors);
+}
+
+#[test]
+fn games_past_their_time_limit_are_refused() {
+    // Two minutes of time attack, and a little over
+    let replay = replay(GameMode::TimeAttack, 7300);
+
+    let errors = refusal(&replay);
+    assert!(errors[0].contains("time limit"), "{:?}", errors);
+}
+
+#[test]
+fn seeking_lands_on_the_same_game_as_playing_through() {
+    let replay = replay(GameMode::Classic, 600);
+
+    // The checksum after every frame played straight through
+    let mut player = ReplayPlayer::new(replay.clone());
+    let mut checksums = Vec::new();
+    while player.step() {
+        checksums.push(player.snapshot().checksum());
+    }
+
+    // Back and forth, landing on, just before and just after snapshots
+    let mut player = ReplayPlayer::new(replay);
+    for target in [500, 0, 119, 120, 121, 360, 250, 599] {
+        player.seek(target);
+        assert_eq!(player.frame(), target);
+        assert!(player.step());
+        assert_eq!(
+            player.snapshot().checksum(),
+            checksums[target],
+            "stepping on from frame {} went wrong",
+            target
+        );
+    }
+}
+
+#[test]
+fn same_run_matches_whenever_it_was_recorded() {
+    let original = replay(GameMode::Survival, 300);
+
+    let mut copy = original.clone();
+    copy.recorded_at += 3600;
+    copy.wave += 1;
+    assert_eq!(copy.run_bytes(), original.run_bytes());
+
+    copy.frames[10].inputs[0].fire = !copy.frames[10].inputs[0].fire;
+    assert_ne!(copy.run_bytes(), original.run_bytes());
+}
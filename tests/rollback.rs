@@ -0,0 +1,320 @@
+// Checks that rollback keeps two peers in step. Two sessions play a match in
+// one process with random scripted inputs, talking over a pretend network
+// that delays, reorders and drops their messages. Once every input has got
+// through, both peers must agree with each other, and with a copy of the game
+// that knew every input up front, on the state at the start of every frame.
+
+use std::time::Duration;
+
+use asteroids::controls::ShipInput;
+use asteroids::game::FIXED_FRAME_RATE;
+use asteroids::rollback::{PeerMessage, RollbackSession};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+struct Options {
+    frames: u32,
+    // In frames
+    latency: u32,
+    jitter: u32,
+    loss: f64,
+    seed: u64,
+}
+
+// One direction of the pretend network
+struct Link {
+    latency: u32,
+    jitter: u32,
+    loss: f64,
+    rng: StdRng,
+    // Encoded messages and the tick they arrive on
+    in_flight: Vec<(u32, Vec<u8>)>,
+}
+
+impl Link {
+    fn new(options: &Options, seed: u64) -> Self {
+        Link {
+            latency: options.latency,
+            jitter: options.jitter,
+            loss: options.loss,
+            rng: StdRng::seed_from_u64(seed),
+            in_flight: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, tick: u32, message: &PeerMessage) {
+        if self.rng.gen_bool(self.loss) {
+            return;
+        }
+
+        let delay = self.latency + self.rng.gen_range(0..=self.jitter);
+        let bytes = bincode::serialize(message).expect("failed to encode message");
+        self.in_flight.push((tick + delay, bytes));
+    }
+
+    fn deliver(&mut self, tick: u32) -> Vec<PeerMessage> {
+        let (arrived, in_flight) = self
+            .in_flight
+            .drain(..)
+            .partition(|(arrival, _)| *arrival <= tick);
+        self.in_flight = in_flight;
+
+        arrived
+            .into_iter()
+            .map(|(_, bytes): (u32, Vec<u8>)| {
+                bincode::deserialize(&bytes).expect("failed to decode message")
+            })
+            .collect()
+    }
+}
+
+// Mashes random controls, holding each combination for a little while
+struct Pilot {
+    rng: StdRng,
+    input: ShipInput,
+    hold: u32,
+}
+
+impl Pilot {
+    fn new(seed: u64) -> Self {
+        Pilot {
+            rng: StdRng::seed_from_u64(seed),
+            input: ShipInput::default(),
+            hold: 0,
+        }
+    }
+
+    fn next(&mut self) -> ShipInput {
+        if self.hold == 0 {
+            let rng = &mut self.rng;
+            self.input = ShipInput {
+                thrust: rng.gen_bool(0.5),
+                reverse: rng.gen_bool(0.1),
+                left: rng.gen_bool(0.3),
+                right: rng.gen_bool(0.3),
+                fire: rng.gen_bool(0.5),
+                hyperspace: rng.gen_bool(0.05),
+                switch_weapon: rng.gen_bool(0.05),
+            };
+            self.hold = rng.gen_range(1..30);
+        }
+
+        self.hold -= 1;
+        self.input
+    }
+}
+
+fn play_match(options: Options) {
+    let mut sessions = [
+        RollbackSession::new(options.seed, 0),
+        RollbackSession::new(options.seed, 1),
+    ];
+    // `links[i]` carries messages to peer `i`
+    let mut links = [
+        Link::new(&options, options.seed + 1),
+        Link::new(&options, options.seed + 2),
+    ];
+    let mut pilots = [Pilot::new(options.seed + 3), Pilot::new(options.seed + 4)];
+    let mut played: [Vec<ShipInput>; 2] = Default::default();
+
+    // Long enough for any sane network to get every input across
+    let give_up = options.frames * 10 + 1000;
+    let mut tick = 0;
+
+    loop {
+        for peer in 0..2 {
+            for message in links[peer].deliver(tick) {
+                sessions[peer].receive(message);
+            }
+
+            let session = &mut sessions[peer];
+            if session.frame() < options.frames && session.can_advance() {
+                let input = pilots[peer].next();
+                played[peer].push(input);
+                session.advance(input);
+            } else {
+                session.sync();
+            }
+
+            links[1 - peer].send(tick, &sessions[peer].outgoing());
+        }
+
+        let finished = sessions
+            .iter()
+            .all(|session| session.frame() == options.frames && session.is_confirmed());
+        if finished {
+            break;
+        }
+
+        tick += 1;
+        assert!(
+            tick <= give_up,
+            "peers still waiting for input after {} ticks",
+            tick
+        );
+    }
+
+    // The same match played with nothing to guess
+    let mut reference = RollbackSession::new(options.seed, 0);
+    reference.receive(PeerMessage::Inputs {
+        start: 0,
+        inputs: played[1].clone(),
+        received: 0,
+    });
+    for input in played[0].iter() {
+        reference.advance(*input);
+    }
+    reference.sync();
+    assert_eq!(reference.stats.rollbacks, 0);
+
+    for (peer, session) in sessions.iter_mut().enumerate() {
+        assert!(
+            session.stats.rollbacks > 0,
+            "peer {} never rolled back",
+            peer + 1
+        );
+
+        let mismatch = reference
+            .checksums()
+            .iter()
+            .zip(session.checksums())
+            .position(|(expected, actual)| expected != actual);
+        assert_eq!(mismatch, None, "peer {} went wrong", peer + 1);
+        assert_eq!(session.checksums().len(), reference.checksums().len());
+        assert_eq!(
+            session.checksum(),
+            reference.checksum(),
+            "peer {} ended up in a different state",
+            peer + 1
+        );
+    }
+}
+
+#[test]
+fn peers_agree_over_a_lossy_network() {
+    for seed in 1..=3 {
+        play_match(Options {
+            frames: 1800,
+            latency: 6,
+            jitter: 4,
+            loss: 0.2,
+            seed,
+        });
+    }
+}
+
+#[test]
+fn peers_agree_when_most_messages_are_lost() {
+    play_match(Options {
+        frames: 1800,
+        latency: 2,
+        jitter: 8,
+        loss: 0.6,
+        seed: 4,
+    });
+}
+
+// Bevy orders the systems nothing orders differently in every app, so the same
+// match played in separate apps is only the same if the game orders everything
+// that matters itself
+#[test]
+fn separate_apps_play_the_same_match() {
+    let mut pilots = [Pilot::new(5), Pilot::new(6)];
+    let inputs: Vec<[ShipInput; 2]> = (0..1800)
+        .map(|_| [pilots[0].next(), pilots[1].next()])
+        .collect();
+
+    let checksums: Vec<Vec<u64>> = (0..4)
+        .map(|_| {
+            let mut session = RollbackSession::new(7, 0);
+            session.receive(PeerMessage::Inputs {
+                start: 0,
+                inputs: inputs.iter().map(|input| input[1]).collect(),
+                received: 0,
+            });
+            for input in inputs.iter() {
+                session.advance(input[0]);
+            }
+            session.sync();
+            session.checksums().to_vec()
+        })
+        .collect();
+
+    for other in checksums.iter().skip(1) {
+        assert_eq!(other.len(), checksums[0].len());
+        let mismatch = other
+            .iter()
+            .zip(checksums[0].iter())
+            .position(|(a, b)| a != b);
+        assert_eq!(mismatch, None, "the apps disagree");
+    }
+}
+
+// Each peer draws at its own uneven rate, with messages arriving as soon as
+// they're sent. However often the peers draw, the match plays at 60 frames a
+// second of the time that has gone by.
+#[test]
+fn match_keeps_to_the_clock_whatever_the_refresh_rate() {
+    let deltas = [
+        vec![
+            Duration::from_secs_f64(1.0 / 240.0),
+            Duration::from_millis(7),
+            Duration::from_secs_f64(1.0 / 30.0),
+            Duration::from_micros(4100),
+        ],
+        vec![
+            Duration::from_secs_f64(1.0 / 144.0),
+            Duration::from_millis(16),
+            Duration::from_millis(50),
+            Duration::from_millis(2),
+            Duration::from_millis(21),
+        ],
+    ];
+    let seconds = Duration::from_secs(10);
+
+    let mut sessions = [RollbackSession::new(8, 0), RollbackSession::new(8, 1)];
+    let mut pilots = [Pilot::new(9), Pilot::new(10)];
+    let mut owed = [Duration::ZERO; 2];
+    // When each peer next draws, and how many times it has
+    let mut clocks = [Duration::ZERO; 2];
+    let mut draws = [0; 2];
+
+    loop {
+        let peer = if clocks[0] <= clocks[1] { 0 } else { 1 };
+        let delta = deltas[peer][draws[peer] % deltas[peer].len()];
+        if clocks[peer] + delta > seconds {
+            break;
+        }
+        clocks[peer] += delta;
+        draws[peer] += 1;
+
+        owed[peer] += delta;
+        let input = pilots[peer].next();
+        sessions[peer].advance_for(&mut owed[peer], input);
+        let message = sessions[peer].outgoing();
+        sessions[1 - peer].receive(message);
+    }
+
+    let frame_time = Duration::from_secs_f64(1.0 / FIXED_FRAME_RATE);
+    for (peer, session) in sessions.iter().enumerate() {
+        let expected = (clocks[peer].as_nanos() / frame_time.as_nanos()) as u32;
+        assert_eq!(session.frame(), expected, "peer {} lost time", peer + 1);
+    }
+
+    // The last few inputs still have to get across before the peers agree
+    for _ in 0..2 {
+        for peer in 0..2 {
+            let message = sessions[peer].outgoing();
+            sessions[1 - peer].receive(message);
+            sessions[1 - peer].sync();
+        }
+    }
+    let [first, second] = &sessions;
+    let agreed = first.checksums().len().min(second.checksums().len());
+    assert!(agreed > 500);
+    assert_eq!(
+        first.checksums()[..agreed],
+        second.checksums()[..agreed],
+        "the peers disagree"
+    );
+}
@@ -0,0 +1,83 @@
+use asteroids::game::{headless_game, GameSystem, Roster};
+use bevy::ecs::schedule::GraphNode;
+use bevy::prelude::*;
+
+const ORDER: [GameSystem; 7] = [
+    GameSystem::Input,
+    GameSystem::Fire,
+    GameSystem::Spawn,
+    GameSystem::Movement,
+    GameSystem::Collision,
+    GameSystem::Damage,
+    GameSystem::Hud,
+];
+
+// The game's systems in the order the update stage resolved them to, each with
+// the position of its `GameSystem` label in `ORDER`, if it has one
+fn resolved_order(app: &App) -> Vec<(String, Option<usize>)> {
+    let stage = app
+        .schedule
+        .get_stage::<SystemStage>(&CoreStage::Update)
+        .unwrap();
+
+    stage
+        .parallel_systems()
+        .iter()
+        .map(|system| {
+            let name = GraphNode::name(system).into_owned();
+            let label = ORDER.iter().position(|label| {
+                let label = label.dyn_clone();
+                system.labels().iter().any(|other| **other == *label)
+            });
+            (name, label)
+        })
+        .collect()
+}
+
+fn position(order: &[(String, Option<usize>)], system: &str) -> usize {
+    order
+        .iter()
+        .position(|(name, _)| name.ends_with(&format!("::{}", system)))
+        .unwrap_or_else(|| panic!("{} is not scheduled", system))
+}
+
+#[test]
+fn game_systems_run_in_label_order() {
+    let mut app = headless_game(1, Roster::default());
+    // Stages only sort their systems when they first run
+    app.update();
+    let order = resolved_order(&app);
+
+    let labels: Vec<usize> = order.iter().filter_map(|(_, label)| *label).collect();
+    for (index, label) in ORDER.iter().enumerate() {
+        assert!(labels.contains(&index), "nothing runs in {:?}", label);
+    }
+    for (earlier, later) in labels.iter().zip(labels.iter().skip(1)) {
+        assert!(earlier <= later, "labels out of order: {:?}", order);
+    }
+}
+
+#[test]
+fn game_systems_run_after_what_they_depend_on() {
+    let mut app = headless_game(1, Roster::default());
+    app.update();
+    let order = resolved_order(&app);
+
+    for (earlier, later) in [
+        ("pick_up_power_ups", "asteroid_collision"),
+        ("asteroid_collision", "asteroid_bounce"),
+        ("asteroid_collision", "bullet_collision"),
+        ("award_points", "player_death"),
+        ("player_death", "match_end"),
+        ("award_points", "tick_effects"),
+        ("bullet_fire", "steer_missiles"),
+        ("steer_missiles", "bullet_movement"),
+    ] {
+        assert!(
+            position(&order, earlier) < position(&order, later),
+            "{} should run before {}",
+            earlier,
+            later
+        );
+    }
+}